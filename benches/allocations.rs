@@ -0,0 +1,212 @@
+//! Counts the allocations of rendering every line of the sample logs with `print_log_line` and compares them with the
+//! code path fblog used before the entries were flattened lazily: flattening the whole entry into strings and rendering
+//! a clone of the entry with the `fblog_*` variables inserted.
+//!
+//! Run with `cargo bench --bench allocations`. The sources of fblog are compiled into the bench, as fblog has no library.
+
+// Only `print_log_line` is used, and the test modules of the sources are compiled without their test harness
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/app.rs"]
+mod app;
+#[path = "../src/autodetect.rs"]
+mod autodetect;
+#[path = "../src/clef.rs"]
+mod clef;
+#[path = "../src/compiled_template.rs"]
+mod compiled_template;
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/continuation.rs"]
+mod continuation;
+#[path = "../src/cri.rs"]
+mod cri;
+#[path = "../src/decompress.rs"]
+mod decompress;
+#[path = "../src/embedded.rs"]
+mod embedded;
+#[path = "../src/encoding.rs"]
+mod encoding;
+#[path = "../src/explode.rs"]
+mod explode;
+#[path = "../src/filter.rs"]
+mod filter;
+#[path = "../src/input.rs"]
+mod input;
+#[path = "../src/json_stream.rs"]
+mod json_stream;
+#[path = "../src/level.rs"]
+mod level;
+#[path = "../src/log.rs"]
+mod log;
+#[path = "../src/log_settings.rs"]
+mod log_settings;
+#[path = "../src/logfmt.rs"]
+mod logfmt;
+#[path = "../src/pattern.rs"]
+mod pattern;
+#[path = "../src/pipeline.rs"]
+mod pipeline;
+#[path = "../src/preset.rs"]
+mod preset;
+#[path = "../src/process.rs"]
+mod process;
+#[path = "../src/stack_trace.rs"]
+mod stack_trace;
+#[path = "../src/substitution.rs"]
+mod substitution;
+#[path = "../src/syslog.rs"]
+mod syslog;
+#[path = "../src/template.rs"]
+mod template;
+#[path = "../src/time.rs"]
+mod time;
+#[path = "../src/time_range.rs"]
+mod time_range;
+
+use handlebars::Handlebars;
+use indexmap::IndexMap;
+use log::{EntryContext, FlatEntry};
+use log_settings::LogSettings;
+use serde_json::{Map, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use substitution::Substitution;
+use template::Templates;
+
+const SAMPLE_LOGS: &[&str] = &[
+	"sample.json.log",
+	"sample_nested.json.log",
+	"sample_numbered.json.log",
+	"sample_context.log",
+	"sample_elastic.log",
+];
+
+/// Counts allocations and reallocations of the whole process.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		unsafe { System.alloc(layout) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		unsafe { System.dealloc(ptr, layout) }
+	}
+
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		unsafe { System.realloc(ptr, layout, new_size) }
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn main() {
+	let log_settings = LogSettings::new_default_settings();
+	let templates = Templates::new(
+		template::DEFAULT_MAIN_LINE_FORMAT.to_string(),
+		template::DEFAULT_ADDITIONAL_VALUE_FORMAT.to_string(),
+		Substitution::default(),
+	);
+	let handlebars = template::fblog_handlebar_registry(
+		template::DEFAULT_MAIN_LINE_FORMAT.to_string(),
+		template::DEFAULT_ADDITIONAL_VALUE_FORMAT.to_string(),
+		Substitution::default(),
+	);
+
+	println!("{:<28} {:>6} {:>16} {:>16}", "sample", "lines", "allocs/line", "baseline/line");
+	for sample_log in SAMPLE_LOGS {
+		let log_entries = sample_entries(sample_log);
+		let mut out = io::sink();
+		let allocations = count_allocations(|| {
+			for log_entry in &log_entries {
+				log::print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(log_entry), &log_settings, &templates).unwrap();
+			}
+		});
+		let baseline_allocations = count_allocations(|| {
+			for log_entry in &log_entries {
+				baseline_print_log_line(&mut out, log_entry, &log_settings, &handlebars).unwrap();
+			}
+		});
+		let lines = log_entries.len() as f64;
+		println!(
+			"{sample_log:<28} {:>6} {:>16.1} {:>16.1}",
+			log_entries.len(),
+			allocations as f64 / lines,
+			baseline_allocations as f64 / lines
+		);
+	}
+}
+
+fn sample_entries(sample_log: &str) -> Vec<Map<String, Value>> {
+	let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(sample_log);
+	fs::read_to_string(path)
+		.expect("Should be able to read the sample log")
+		.lines()
+		.filter_map(|line| match serde_json::from_str(line) {
+			Ok(Value::Object(log_entry)) => Some(log_entry),
+			_ => None,
+		})
+		.collect()
+}
+
+fn count_allocations(render: impl FnOnce()) -> usize {
+	let before = ALLOCATIONS.load(Ordering::Relaxed);
+	render();
+	ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// The rendering before the lazy `FlatEntry`, for entries without additional values.
+fn baseline_print_log_line(out: &mut dyn Write, log_entry: &Map<String, Value>, log_settings: &LogSettings, handlebars: &Handlebars) -> io::Result<()> {
+	let flattened = baseline_flatten(log_entry, "");
+	let value = |keys: &[String], default: &str| keys.iter().find_map(|key| flattened.get(key).cloned()).unwrap_or_else(|| default.to_string());
+	let level = value(&log_settings.level_keys, "unknown");
+	let level = log_settings.level_map.get(&level).cloned().unwrap_or(level);
+	let message = value(&log_settings.message_keys, "");
+	let timestamp = time::try_convert_timestamp_to_readable(value(&log_settings.time_keys, ""), log_settings.epoch_unit);
+
+	let mut template_data = log_entry.clone();
+	template_data.insert("fblog_timestamp".to_string(), Value::String(timestamp));
+	template_data.insert("fblog_level".to_string(), Value::String(level));
+	template_data.insert("fblog_message".to_string(), Value::String(message));
+	template_data.insert("fblog_prefix".to_string(), Value::String(String::new()));
+	match handlebars.render("main_line", &template_data) {
+		Ok(string) => writeln!(out, "{string}"),
+		Err(e) => writeln!(out, "Failed to process line: {e}"),
+	}
+}
+
+fn baseline_flatten(log_entry: &Map<String, Value>, prefix: &str) -> IndexMap<String, String> {
+	let mut flattened = IndexMap::new();
+	for (key, value) in log_entry {
+		match value {
+			Value::String(string_value) => {
+				flattened.insert(format!("{prefix}{key}"), string_value.to_string());
+			}
+			Value::Object(nested_entry) => flattened.extend(baseline_flatten(nested_entry, &format!("{prefix}{key} > "))),
+			Value::Array(array_values) => {
+				for (index, array_value) in array_values.iter().enumerate() {
+					match array_value {
+						Value::Object(nested_entry) => flattened.extend(baseline_flatten(nested_entry, &format!("{prefix}{key}[{index}] > "))),
+						_ => {
+							flattened.insert(format!("{prefix}{key}[{index}]"), array_value.to_string());
+						}
+					}
+				}
+			}
+			Value::Null => (),
+			_ => {
+				flattened.insert(format!("{prefix}{key}"), value.to_string());
+			}
+		}
+	}
+	flattened
+}
@@ -0,0 +1,77 @@
+//! Renders the sample logs with the fblog binary and reports the throughput.
+//!
+//! Only the wall time of whole runs of the binary is measured, including the startup and reading the input.
+//! The allocations of `print_log_line` are counted in-process by the `allocations` bench. The startup is reported as the time of a run on an empty input.
+//!
+//! Run with `cargo bench`. Without `--bench` (e.g. in `cargo test --all-targets`) every case runs once on a small input.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const SAMPLE_LOGS: &[&str] = &[
+	"sample.json.log",
+	"sample_nested.json.log",
+	"sample_numbered.json.log",
+	"sample_context.log",
+	"sample_elastic.log",
+];
+
+const CASES: &[(&str, &[&str])] = &[
+	("default", &["--threads", "1"]),
+	("additional values", &["--threads", "1", "-a", "process", "-a", "nested"]),
+	("dump all", &["--threads", "1", "-d"]),
+	("filter", &["--threads", "1", "-f", r#"level == "info""#]),
+	("prefix", &["--threads", "1", "-p"]),
+	("all threads", &[]),
+];
+
+fn main() {
+	let bench = env::args().any(|arg| arg == "--bench");
+	let (repetitions, iterations) = if bench { (5_000, 3) } else { (10, 1) };
+
+	let empty_input = env::temp_dir().join(format!("fblog-bench-empty-{}.log", std::process::id()));
+	fs::write(&empty_input, "").expect("Should be able to write the input");
+	let startup = (0..iterations).map(|_| run(&[], &empty_input)).min().expect("At least one iteration");
+	println!("{:<20} {:>8.1} ms", "startup", startup.as_secs_f64() * 1000.0);
+	fs::remove_file(empty_input).expect("Should be able to remove the input");
+
+	let input = sample_input(repetitions);
+	let lines = fs::read_to_string(&input).expect("Should be able to read the input").lines().count();
+
+	for (name, args) in CASES {
+		let fastest = (0..iterations).map(|_| run(args, &input)).min().expect("At least one iteration");
+		let lines_per_second = lines as f64 / fastest.as_secs_f64();
+		println!("{name:<20} {:>8.1} ms {lines_per_second:>12.0} lines/s", fastest.as_secs_f64() * 1000.0);
+	}
+
+	fs::remove_file(input).expect("Should be able to remove the input");
+}
+
+/// Writes the sample logs repeatedly into one input file.
+fn sample_input(repetitions: usize) -> PathBuf {
+	let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	let samples: String = SAMPLE_LOGS
+		.iter()
+		.map(|sample_log| fs::read_to_string(root.join(sample_log)).expect("Should be able to read the sample log"))
+		.collect();
+
+	let input = env::temp_dir().join(format!("fblog-bench-{}.log", std::process::id()));
+	fs::write(&input, samples.repeat(repetitions)).expect("Should be able to write the input");
+	input
+}
+
+fn run(args: &[&str], input: &PathBuf) -> Duration {
+	let start = Instant::now();
+	let status = Command::new(env!("CARGO_BIN_EXE_fblog"))
+		.args(args)
+		.arg(input)
+		.env("NO_COLOR", "1")
+		.stdout(Stdio::null())
+		.status()
+		.expect("Should be able to run fblog");
+	assert!(status.success(), "fblog {args:?} failed");
+	start.elapsed()
+}
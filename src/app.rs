@@ -1,3 +1,4 @@
+use crate::continuation;
 use crate::substitution::Substitution;
 use clap::builder::Styles;
 use clap::builder::styling::{AnsiColor, Effects};
@@ -56,6 +57,13 @@ pub fn app() -> Command {
 				.num_args(1)
 				.help("Adds an additional key to detect the message in the log entry. The first matching key will be assigned to `fblog_message`."),
 		)
+		.arg(
+			Arg::new("stack-trace-key")
+				.long("stack-trace-key")
+				.action(ArgAction::Append)
+				.num_args(1)
+				.help("Adds an additional key of stack traces, which are printed indented below the log entry."),
+		)
 		.arg(
 			Arg::new("print-lua")
 				.long("print-lua")
@@ -113,6 +121,29 @@ pub fn app() -> Command {
 				.action(ArgAction::SetTrue)
 				.help("consider all text before opening curly brace as prefix"),
 		)
+		.arg(
+			Arg::new("prefix-regex")
+				.long("prefix-regex")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("regex")
+				.help("Regular expression matching the prefix of a line, e.g. `^\\[pod/(?P<pod>[^/]+)/(?P<container>[^\\]]+)\\] `. Named capture groups become fields of the log entry."),
+		)
+		.arg(
+			Arg::new("continuation")
+				.long("continuation")
+				.action(ArgAction::SetTrue)
+				.help("Attach lines that are no log entries and match the continuation regex to the previous line as `fblog_continuation`, e.g. stack traces."),
+		)
+		.arg(
+			Arg::new("continuation-regex")
+				.long("continuation-regex")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("regex")
+				.default_value_if("continuation", "true", continuation::DEFAULT_PATTERN)
+				.help("Regular expression matching continuation lines, implies --continuation."),
+		)
 		.arg(
 			Arg::new("filter")
 				.long("filter")
@@ -130,11 +161,130 @@ pub fn app() -> Command {
 		)
 		.arg(
 			Arg::new("INPUT")
-				.help("Sets the input file to use, otherwise assumes stdin")
+				.help("Sets the input files to use, otherwise assumes stdin. Multiple files are merged by the timestamps of their log entries.")
 				.required(false)
+				.action(ArgAction::Append)
+				.num_args(1..)
 				.value_hint(ValueHint::AnyPath)
 				.default_value("-"),
 		)
+		.arg(
+			Arg::new("input-format")
+				.long("input-format")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_parser(["json", "json-stream", "logfmt", "syslog"])
+				.default_value("json")
+				.help("How log entries are read. `json` expects one entry per line and falls back to syslog and logfmt for lines that are no json, `json-stream` also reads pretty printed entries spanning multiple lines, multiple entries on one line and json text sequences (RFC 7464), `logfmt` and `syslog` only read lines of this format."),
+		)
+		.arg(
+			Arg::new("threads")
+				.long("threads")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("N")
+				.value_parser(value_parser!(u16).range(1..))
+				.conflicts_with("follow")
+				.help("Number of threads that parse, filter and render log entries. Uses all cores for files and one thread for stdin by default. Output stays in input order."),
+		)
+		.arg(
+			Arg::new("preset")
+				.long("preset")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("name")
+				.help("Sets the message, time and level keys, level map, epoch unit and excluded fields of a logging library: bunyan, pino, zap, logrus, slog, structlog or winston, or a log format: ecs, gelf, clef, journald, otlp, logfmt or syslog. Presets can be changed and added in the `presets` section of the config file."),
+		)
+		.arg(
+			Arg::new("no-autodetect")
+				.long("no-autodetect")
+				.num_args(0)
+				.action(ArgAction::SetTrue)
+				.help("Turns off detecting the preset from the first lines of the input when no `--preset` is given."),
+		)
+		.arg(
+			Arg::new("min-level")
+				.long("min-level")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("level")
+				.conflicts_with("levels")
+				.help("Only shows log entries of this level or a more severe level, e.g. `warn` shows warn, error and fatal. The severity order is configured with `level_order` and `level_aliases` in the config file."),
+		)
+		.arg(
+			Arg::new("levels")
+				.long("levels")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("levels")
+				.value_delimiter(',')
+				.help("Only shows log entries of these comma separated levels, e.g. `error,fatal`."),
+		)
+		.arg(
+			Arg::new("since")
+				.long("since")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("time")
+				.help("Only shows log entries at or after this time. Either a timestamp like `2024-05-21T10:00` (UTC unless a time zone is given) or a duration before now like `15m`, `1h30m` or `2d`."),
+		)
+		.arg(
+			Arg::new("until")
+				.long("until")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("time")
+				.help("Only shows log entries before this time. Accepts the same values as `--since`."),
+		)
+		.arg(
+			Arg::new("unparsed-time")
+				.long("unparsed-time")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_parser(["keep", "drop", "attach"])
+				.default_value("keep")
+				.help("What happens to log entries without a parsable timestamp when `--since` or `--until` is given. `attach` shows them when the previous log entry is shown and processes the input with one thread."),
+		)
+		.arg(
+			Arg::new("encoding")
+				.long("encoding")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_parser(["utf-8", "latin1"])
+				.default_value("utf-8")
+				.help("How lines that are no valid UTF-8 are read. `utf-8` shows invalid bytes escaped (`\\xff`), `latin1` decodes these lines as Latin-1. UTF-16 input with byte order mark is detected automatically."),
+		)
+		.arg(
+			Arg::new("parse-regex")
+				.long("parse-regex")
+				.action(ArgAction::Append)
+				.num_args(1)
+				.value_name("pattern")
+				.help("Parses lines that are no json with a regular expression. Named capture groups become the fields of the log entry. Also accepts the name of a pattern from the config file or one of the builtin patterns: combined, common, logback, log4j2, log4j."),
+		)
+		.arg(
+			Arg::new("parse-embedded-json")
+				.long("parse-embedded-json")
+				.action(ArgAction::Append)
+				.num_args(1)
+				.value_name("key")
+				.help("Parses the string value of this key as json and merges the object into the log entry. The `log` value of docker json-file logs is merged automatically."),
+		)
+		.arg(
+			Arg::new("explode")
+				.long("explode")
+				.num_args(1)
+				.action(ArgAction::Set)
+				.value_name("path")
+				.help("Expands the array at this path (e.g. `events` or `payload.events`) into one log entry per element. Every element gets the fields of its parent."),
+		)
+		.arg(
+			Arg::new("follow")
+				.long("follow")
+				.num_args(0)
+				.action(ArgAction::SetTrue)
+				.help("Keep reading appended data of the input file. Rotated (moved or truncated) files are reopened."),
+		)
 		.arg(
 			Arg::new("main-line-format")
 				.long("main-line-format")
@@ -161,7 +311,7 @@ pub fn app() -> Command {
 				.num_args(1)
 				.action(ArgAction::Set)
 				.default_value_if("enable-substitution", "true", Substitution::DEFAULT_CONTEXT_KEY)
-				.help("Use this key as the source of substitutions for the message. Value can either be an array ({1}) or an object ({key})."),
+				.help("Use this key as the source of substitutions for the message. Value can either be an array ({1}, {} or %s) or an object ({key} or {nested.key[0]}). Top-level fields are used if the context does not have a key."),
 		)
 		.arg(
 			Arg::new("placeholder-format")
@@ -170,6 +320,6 @@ pub fn app() -> Command {
 				.num_args(1)
 				.action(ArgAction::Set)
 				.default_value_if("enable-substitution", "true", Substitution::DEFAULT_PLACEHOLDER_FORMAT)
-				.help("The format that should be used for substituting values in the message, where the key is the literal word `key`. Example: [[key]] or ${key}."),
+				.help("The format that should be used for substituting values in the message, where the key is the literal word `key`. Example: [[key]] or ${key}. Use %s for printf style placeholders like %s, %d or %.2f."),
 		)
 }
@@ -0,0 +1,221 @@
+use crate::cri;
+use crate::logfmt;
+use crate::syslog;
+use serde_json::{Map, Value};
+use std::io::{self, Read};
+
+/// Number of log entries of the input that are compared with the known formats.
+const SAMPLE_SIZE: usize = 50;
+/// Upper limit of the bytes read for a sample.
+const SAMPLE_BYTES: usize = 64 * 1024;
+
+/// The first lines of an input, read the way `--input-format json` reads them.
+enum Sample {
+	Json(Map<String, Value>),
+	Logfmt,
+	Syslog,
+	Text,
+}
+
+type Matches = fn(&Sample) -> bool;
+
+/// Known formats and the presets used for them, more specific formats first.
+static PROFILES: &[(&str, Matches)] = &[
+	("bunyan", is_bunyan),
+	("pino", is_pino),
+	("ecs", is_ecs),
+	("gelf", is_gelf),
+	("clef", is_clef),
+	("journald", is_journald),
+	("otlp", is_otlp),
+	("logfmt", |sample| matches!(sample, Sample::Logfmt)),
+	("syslog", |sample| matches!(sample, Sample::Syslog)),
+];
+
+/// Returns the preset of the format most sampled lines have, if at least half of them have it.
+/// Only complete lines of the sampled texts, the beginnings of the inputs, are used.
+pub fn detect<S: AsRef<str>>(texts: &[S]) -> Option<&'static str> {
+	let samples: Vec<Sample> = texts
+		.iter()
+		.flat_map(|text| complete_lines(text.as_ref()).lines())
+		.map(|line| cri::parse(line).map(|cri_line| cri_line.content).unwrap_or(line).trim())
+		.filter(|line| !line.is_empty())
+		.take(SAMPLE_SIZE)
+		.map(sample)
+		.collect();
+
+	let (name, count) = PROFILES
+		.iter()
+		.map(|(name, matches)| (*name, samples.iter().filter(|sample| matches(sample)).count()))
+		.rev()
+		.max_by_key(|(_, count)| *count)?;
+	(count > 0 && count * 2 >= samples.len()).then_some(name)
+}
+
+/// Reads the beginning of an input for [`detect`]. Stops at the end of the input, after enough lines or when
+/// a complete line was read and no more data is available right now, so streams are not delayed.
+pub fn read_sample(input: &mut dyn Read) -> Vec<u8> {
+	let mut sample = Vec::new();
+	let mut chunk = [0u8; 8 * 1024];
+	while sample.len() < SAMPLE_BYTES && sample.iter().filter(|byte| **byte == b'\n').count() < SAMPLE_SIZE {
+		let read = match input.read(&mut chunk) {
+			Ok(0) => break,
+			Ok(read) => read,
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			// The error is reported when the input is processed
+			Err(_) => break,
+		};
+		sample.extend_from_slice(&chunk[..read]);
+		if read < chunk.len() && sample.contains(&b'\n') {
+			break;
+		}
+	}
+	sample
+}
+
+fn complete_lines(text: &str) -> &str {
+	match text.rfind('\n') {
+		Some(end) => &text[..end],
+		None => text,
+	}
+}
+
+fn sample(line: &str) -> Sample {
+	if let Ok(Value::Object(log_entry)) = serde_json::from_str(line) {
+		Sample::Json(log_entry)
+	} else if syslog::parse(line).is_some() {
+		Sample::Syslog
+	} else if logfmt::parse_detected(line).is_some() {
+		Sample::Logfmt
+	} else {
+		Sample::Text
+	}
+}
+
+fn json(sample: &Sample) -> Option<&Map<String, Value>> {
+	match sample {
+		Sample::Json(log_entry) => Some(log_entry),
+		_ => None,
+	}
+}
+
+fn has_numeric_level(log_entry: &Map<String, Value>) -> bool {
+	log_entry.get("level").and_then(Value::as_u64).is_some_and(|level| (10..=60).contains(&level)) && log_entry.contains_key("msg")
+}
+
+fn is_bunyan(sample: &Sample) -> bool {
+	json(sample).is_some_and(|log_entry| {
+		has_numeric_level(log_entry) && log_entry.get("v").is_some_and(Value::is_number) && log_entry.get("time").is_some_and(Value::is_string)
+	})
+}
+
+fn is_pino(sample: &Sample) -> bool {
+	json(sample).is_some_and(|log_entry| has_numeric_level(log_entry) && log_entry.get("time").is_some_and(Value::is_number))
+}
+
+fn is_ecs(sample: &Sample) -> bool {
+	json(sample).is_some_and(|log_entry| {
+		log_entry.contains_key("@timestamp")
+			&& (log_entry.contains_key("ecs.version")
+				|| log_entry.get("ecs").is_some_and(Value::is_object)
+				|| log_entry.contains_key("log.level")
+				|| log_entry.get("log").and_then(|log| log.get("level")).is_some())
+	})
+}
+
+fn is_gelf(sample: &Sample) -> bool {
+	json(sample).is_some_and(|log_entry| {
+		log_entry.get("version").and_then(Value::as_str) == Some("1.1") && log_entry.contains_key("host") && log_entry.contains_key("short_message")
+	})
+}
+
+fn is_clef(sample: &Sample) -> bool {
+	json(sample).is_some_and(|log_entry| log_entry.contains_key("@t") && (log_entry.contains_key("@m") || log_entry.contains_key("@mt")))
+}
+
+fn is_journald(sample: &Sample) -> bool {
+	json(sample).is_some_and(|log_entry| log_entry.contains_key("__REALTIME_TIMESTAMP") && log_entry.contains_key("MESSAGE"))
+}
+
+fn is_otlp(sample: &Sample) -> bool {
+	json(sample).is_some_and(|log_entry| {
+		(log_entry.contains_key("timeUnixNano") || log_entry.contains_key("observedTimeUnixNano"))
+			&& (log_entry.contains_key("severityText") || log_entry.contains_key("severityNumber") || log_entry.contains_key("body"))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn detect_json_formats() {
+		let cases = [
+			(
+				"bunyan",
+				r#"{"name":"api","hostname":"box","pid":1,"level":30,"msg":"started","time":"2024-05-21T11:21:29.536Z","v":0}"#,
+			),
+			("pino", r#"{"level":30,"time":1716290489536,"pid":1,"hostname":"box","msg":"started"}"#),
+			(
+				"ecs",
+				r#"{"@timestamp":"2024-05-21T11:21:29.536Z","log.level":"info","message":"started","ecs.version":"1.6.0"}"#,
+			),
+			("ecs", r#"{"@timestamp":"2024-05-21T11:21:29.536Z","log":{"level":"info"},"message":"started"}"#),
+			(
+				"gelf",
+				r#"{"version":"1.1","host":"box","short_message":"started","timestamp":1716290489.536,"level":6}"#,
+			),
+			("clef", r#"{"@t":"2024-05-21T11:21:29.536Z","@mt":"Started {App}","App":"api"}"#),
+			("journald", r#"{"__REALTIME_TIMESTAMP":"1716290489536000","PRIORITY":"6","MESSAGE":"started"}"#),
+			(
+				"otlp",
+				r#"{"timeUnixNano":"1716290489536000000","severityText":"INFO","body":{"stringValue":"started"}}"#,
+			),
+		];
+		for (name, line) in cases {
+			assert_eq!(detect(&[&format!("{line}\n{line}\n")]), Some(name), "{line}");
+		}
+	}
+
+	#[test]
+	fn detect_text_formats() {
+		assert_eq!(
+			detect(&["ts=2024-05-21T11:21:29Z level=info msg=started\nts=2024-05-21T11:21:30Z level=info msg=ready\n"]),
+			Some("logfmt")
+		);
+		assert_eq!(detect(&["<34>1 2024-05-21T11:21:29.536Z box api 1 - - started\n"]), Some("syslog"));
+		assert_eq!(
+			detect(&["2024-05-21T11:21:29.536Z stdout F {\"level\":30,\"time\":1716290489536,\"msg\":\"started\"}\n"]),
+			Some("pino")
+		);
+	}
+
+	#[test]
+	fn detect_nothing() {
+		assert_eq!(detect(&[""]), None);
+		assert_eq!(detect(&["plain text\nmore text\n"]), None);
+		assert_eq!(detect(&[r#"{"level":"info","msg":"started"}"#]), None);
+		let mostly_text = "text\ntext\ntext\n{\"level\":30,\"time\":1716290489536,\"msg\":\"started\"}\n";
+		assert_eq!(detect(&[mostly_text]), None);
+	}
+
+	#[test]
+	fn read_sample_of_input() {
+		let pino = r#"{"level":30,"time":1716290489536,"msg":"started"}"#;
+		let lines = format!("{pino}\n").repeat(SAMPLE_SIZE * 2);
+		// The first read returns one byte like the reader of a decoded input
+		let mut input = io::Cursor::new(b"{".to_vec()).chain(io::Cursor::new(&lines.as_bytes()[1..]));
+		let sample = read_sample(&mut input);
+		assert!(sample.starts_with(pino.as_bytes()));
+		assert!(sample.iter().filter(|byte| **byte == b'\n').count() >= SAMPLE_SIZE);
+
+		let mut stream = io::Cursor::new(b"{".to_vec()).chain(io::Cursor::new(format!("{}\n", &pino[1..]).into_bytes()));
+		assert_eq!(read_sample(&mut stream), format!("{pino}\n").into_bytes());
+	}
+
+	#[test]
+	fn ignore_incomplete_last_line() {
+		let pino = r#"{"level":30,"time":1716290489536,"msg":"started"}"#;
+		assert_eq!(detect(&[&format!("{pino}\n{{\"level\":30,\"ti")]), Some("pino"));
+	}
+}
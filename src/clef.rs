@@ -0,0 +1,247 @@
+use serde_json::{Map, Number, Value};
+
+/// Level of CLEF events without `@l`, Serilog's `Information`.
+pub static DEFAULT_LEVEL: &str = "info";
+
+/// Whether the log entry is an event of the compact log event format (CLEF) of Serilog.
+pub fn is_clef(log_entry: &Map<String, Value>) -> bool {
+	log_entry.contains_key("@t") && (log_entry.contains_key("@mt") || log_entry.contains_key("@m"))
+}
+
+/// Renders a message template like `User {UserId} logged in after {Elapsed:0.00} ms` with the top-level properties
+/// of the log entry, the way Serilog renders messages. Tokens of missing properties are kept as they are.
+pub fn render_message_template(template: &str, properties: &Map<String, Value>) -> String {
+	let mut rendered = String::with_capacity(template.len());
+	let mut rest = template;
+	while let Some(start) = rest.find(['{', '}']) {
+		rendered.push_str(&rest[..start]);
+		rest = &rest[start..];
+		if rest.starts_with("{{") || rest.starts_with("}}") {
+			rendered.push_str(&rest[..1]);
+			rest = &rest[2..];
+			continue;
+		}
+		let token_end = match rest[1..].find(['{', '}']) {
+			Some(end) if rest.starts_with('{') && rest[1 + end..].starts_with('}') => 1 + end + 1,
+			_ => {
+				rendered.push_str(&rest[..1]);
+				rest = &rest[1..];
+				continue;
+			}
+		};
+		let token = &rest[..token_end];
+		match Token::parse(&token[1..token_end - 1]).and_then(|token| token.render(properties)) {
+			Some(value) => rendered.push_str(&value),
+			None => rendered.push_str(token),
+		}
+		rest = &rest[token_end..];
+	}
+	rendered.push_str(rest);
+	rendered
+}
+
+/// A property token like `{@User}`, `{Count,5}` or `{Elapsed:0.00}`.
+struct Token<'a> {
+	name: &'a str,
+	alignment: Option<i64>,
+	format: Option<&'a str>,
+}
+
+impl<'a> Token<'a> {
+	fn parse(token: &'a str) -> Option<Token<'a>> {
+		let (token, format) = match token.split_once(':') {
+			Some((token, format)) => (token, Some(format)),
+			None => (token, None),
+		};
+		let (name, alignment) = match token.split_once(',') {
+			Some((name, alignment)) => (name, Some(alignment.trim().parse().ok()?)),
+			None => (token, None),
+		};
+		// `@` destructures objects and `$` stringifies them, in CLEF both are already part of the property value
+		let name = name.strip_prefix(['@', '$']).unwrap_or(name);
+		if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+			return None;
+		}
+		Some(Token { name, alignment, format })
+	}
+
+	fn render(&self, properties: &Map<String, Value>) -> Option<String> {
+		let value = properties.get(self.name)?;
+		let rendered = match (value, self.format) {
+			(Value::String(string), Some("l")) => string.clone(),
+			(Value::Number(number), Some(format)) => format_number(number, format).unwrap_or_else(|| number.to_string()),
+			_ => render_value(value),
+		};
+		Some(match self.alignment {
+			Some(width) if width < 0 => format!("{rendered:<width$}", width = width.unsigned_abs() as usize),
+			Some(width) => format!("{rendered:>width$}", width = width as usize),
+			None => rendered,
+		})
+	}
+}
+
+/// Renders property values like Serilog: strings quoted, structures as `Type { Name: "value" }`.
+fn render_value(value: &Value) -> String {
+	match value {
+		Value::String(string) => format!("\"{}\"", string.replace('"', "\\\"")),
+		Value::Bool(true) => "True".to_string(),
+		Value::Bool(false) => "False".to_string(),
+		Value::Null => "null".to_string(),
+		Value::Number(number) => number.to_string(),
+		Value::Array(values) => format!("[{}]", values.iter().map(render_value).collect::<Vec<_>>().join(", ")),
+		Value::Object(object) => {
+			let properties: Vec<String> = object
+				.iter()
+				.filter(|(key, _)| *key != "$type")
+				.map(|(key, value)| format!("{key}: {}", render_value(value)))
+				.collect();
+			let properties = if properties.is_empty() {
+				" ".to_string()
+			} else {
+				format!(" {} ", properties.join(", "))
+			};
+			match object.get("$type").and_then(Value::as_str) {
+				Some(type_name) => format!("{type_name} {{{properties}}}"),
+				None => format!("{{{properties}}}"),
+			}
+		}
+	}
+}
+
+/// Formats a number with a .NET format string: the standard formats `F2`, `N2`, `D5` and `X`, or custom formats
+/// like `0.00`, `0.##` or `#,##0`. Returns `None` for other formats.
+fn format_number(number: &Number, format: &str) -> Option<String> {
+	let value = number.as_f64()?;
+	let mut chars = format.chars();
+	let specifier = chars.next()?;
+	let precision = chars.as_str();
+	if specifier.is_ascii_alphabetic() && precision.chars().all(|c| c.is_ascii_digit()) {
+		let precision: Option<usize> = precision.parse().ok();
+		return match specifier {
+			'F' | 'f' => Some(fixed(value, precision.unwrap_or(2))),
+			'N' | 'n' => Some(group_thousands(&fixed(value, precision.unwrap_or(2)))),
+			'D' | 'd' => {
+				let integer = number.as_i64()?;
+				let digits = format!("{:0width$}", integer.unsigned_abs(), width = precision.unwrap_or(0));
+				Some(if integer < 0 { format!("-{digits}") } else { digits })
+			}
+			'X' => Some(format!("{:0width$X}", number.as_u64()?, width = precision.unwrap_or(0))),
+			'x' => Some(format!("{:0width$x}", number.as_u64()?, width = precision.unwrap_or(0))),
+			_ => None,
+		};
+	}
+	if !format.chars().all(|c| matches!(c, '0' | '#' | '.' | ',')) {
+		return None;
+	}
+	let (integer_format, decimal_format) = format.split_once('.').unwrap_or((format, ""));
+	let min_decimals = decimal_format.chars().filter(|c| *c == '0').count();
+	let max_decimals = min_decimals + decimal_format.chars().filter(|c| *c == '#').count();
+	let mut rendered = fixed(value, max_decimals);
+	if max_decimals > min_decimals {
+		let trimmed = rendered.trim_end_matches('0').len().max(rendered.len() - (max_decimals - min_decimals));
+		rendered.truncate(trimmed);
+		if rendered.ends_with('.') {
+			rendered.pop();
+		}
+	}
+	let min_integer_digits = integer_format.chars().filter(|c| *c == '0').count();
+	let (sign, digits) = match rendered.strip_prefix('-') {
+		Some(digits) => ("-", digits),
+		None => ("", rendered.as_str()),
+	};
+	let integer_digits = digits.find('.').unwrap_or(digits.len());
+	let padded = format!("{sign}{}{digits}", "0".repeat(min_integer_digits.saturating_sub(integer_digits)));
+	Some(if integer_format.contains(',') { group_thousands(&padded) } else { padded })
+}
+
+/// Rounds half away from zero like .NET.
+fn fixed(value: f64, decimals: usize) -> String {
+	let factor = 10f64.powi(decimals as i32);
+	format!("{:.decimals$}", (value * factor).round() / factor)
+}
+
+fn group_thousands(number: &str) -> String {
+	let (sign, number) = match number.strip_prefix('-') {
+		Some(number) => ("-", number),
+		None => ("", number),
+	};
+	let (integer, decimals) = match number.split_once('.') {
+		Some((integer, decimals)) => (integer, Some(decimals)),
+		None => (number, None),
+	};
+	let mut grouped = String::with_capacity(number.len() + integer.len() / 3 + 1);
+	for (index, digit) in integer.chars().enumerate() {
+		if index > 0 && (integer.len() - index) % 3 == 0 {
+			grouped.push(',');
+		}
+		grouped.push(digit);
+	}
+	match decimals {
+		Some(decimals) => format!("{sign}{grouped}.{decimals}"),
+		None => format!("{sign}{grouped}"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn render(template: &str, properties: Value) -> String {
+		let Value::Object(properties) = properties else { panic!("not an object") };
+		render_message_template(template, &properties)
+	}
+
+	#[test]
+	fn render_properties() {
+		let properties = json!({"UserId": 42, "Name": "Ann", "Admin": true, "Missing": null, "Roles": ["a", "b"]});
+		assert_eq!(
+			render("User {UserId} {Name} {Name:l} {Admin} {Missing} {Roles}", properties.clone()),
+			r#"User 42 "Ann" Ann True null ["a", "b"]"#
+		);
+		assert_eq!(
+			render("{{literal}} {Unknown} {not a token} } {", properties.clone()),
+			"{literal} {Unknown} {not a token} } {"
+		);
+		assert_eq!(render("[{Name,6}] [{Name,-6}] [{UserId,4}]", properties), r#"[ "Ann"] ["Ann" ] [  42]"#);
+	}
+
+	#[test]
+	fn render_hints() {
+		let properties = json!({"User": {"$type": "User", "Id": 1, "Name": "Ann"}, "Point": {"X": 1}, "Empty": {}, "Text": "User 1"});
+		assert_eq!(
+			render("{@User} {@Point} {@Empty} {$Text}", properties),
+			r#"User { Id: 1, Name: "Ann" } { X: 1 } { } "User 1""#
+		);
+	}
+
+	#[test]
+	fn format_numbers() {
+		let properties = json!({"Elapsed": 12.3456, "Count": 1234567, "Negative": -3.5, "Small": 7, "Ratio": 0.5});
+		assert_eq!(
+			render("{Elapsed:0.00} {Elapsed:0.#} {Elapsed:0.####} {Ratio:0.##}", properties.clone()),
+			"12.35 12.3 12.3456 0.5"
+		);
+		assert_eq!(
+			render("{Elapsed:F1} {Count:N0} {Count:#,##0} {Negative:N2}", properties.clone()),
+			"12.3 1,234,567 1,234,567 -3.50"
+		);
+		assert_eq!(
+			render("{Small:D3} {Small:000} {Count:X} {Small:x2} {Negative:0}", properties.clone()),
+			"007 007 12D687 07 -4"
+		);
+		assert_eq!(render("{Elapsed:yyyy} {Elapsed:D3}", properties), "12.3456 12.3456");
+	}
+
+	#[test]
+	fn detect_clef() {
+		let Value::Object(clef) = json!({"@t": "2024-05-21T11:21:29.536Z", "@mt": "Started"}) else {
+			panic!()
+		};
+		let Value::Object(other) = json!({"@timestamp": "2024-05-21T11:21:29.536Z", "message": "Started"}) else {
+			panic!()
+		};
+		assert!(is_clef(&clef));
+		assert!(!is_clef(&other));
+	}
+}
@@ -0,0 +1,423 @@
+use crate::template::helpers;
+use handlebars::template::{Parameter, TemplateElement};
+use handlebars::{JsonRender, JsonTruthy, Path, PathSeg, Template};
+use serde_json::Value;
+
+/// Helpers registered by handlebars itself and fblog helpers only rendered by handlebars. A name of a helper without
+/// params is a helper call, not a value.
+static BUILTIN_HELPERS: &[&str] = &[
+	"if",
+	"unless",
+	"each",
+	"with",
+	"lookup",
+	"raw",
+	"log",
+	"eq",
+	"ne",
+	"gt",
+	"gte",
+	"lt",
+	"lte",
+	"and",
+	"or",
+	"not",
+	"len",
+	"lowerCamelCase",
+	"upperCamelCase",
+	"snakeCase",
+	"kebabCase",
+	"shoutySnakeCase",
+	"shoutyKebabCase",
+	"titleCase",
+	"trainCase",
+	"substitute",
+];
+
+/// A value of the data a template is rendered with.
+pub enum Field<'a> {
+	Text(&'a str),
+	Json(&'a Value),
+}
+
+/// The data a compiled template is rendered with, the same data handlebars gets serialized.
+pub trait Fields {
+	fn field(&self, name: &str) -> Option<Field<'_>>;
+}
+
+/// A template that only uses the fblog helpers, `#if` and plain variables, compiled into native code.
+///
+/// Rendering produces the same output as handlebars. Whenever the output could differ, e.g. a helper gets a param of the
+/// wrong type and handlebars would report an error, rendering returns `None` and the line is rendered with handlebars.
+pub struct CompiledTemplate {
+	elements: Vec<Element>,
+}
+
+enum Element {
+	Raw(String),
+	Field(String),
+	Call(Call),
+	If {
+		condition: Expression,
+		include_zero: bool,
+		then: Vec<Element>,
+		otherwise: Vec<Element>,
+	},
+}
+
+enum Expression {
+	Field(String),
+	Literal(Value),
+	Call(Call),
+}
+
+struct Call {
+	helper: Helper,
+	numbers: Vec<Expression>,
+	text: Box<Expression>,
+}
+
+#[derive(Clone, Copy)]
+enum Helper {
+	Bold,
+	Cyan,
+	Yellow,
+	Red,
+	Blue,
+	Purple,
+	Green,
+	ColorRgb,
+	Uppercase,
+	LevelStyle,
+	FixedSize,
+	MinSize,
+}
+
+impl Helper {
+	fn from_name(name: &str) -> Option<Helper> {
+		Some(match name {
+			"bold" => Helper::Bold,
+			"cyan" => Helper::Cyan,
+			"yellow" => Helper::Yellow,
+			"red" => Helper::Red,
+			"blue" => Helper::Blue,
+			"purple" => Helper::Purple,
+			"green" => Helper::Green,
+			"color_rgb" => Helper::ColorRgb,
+			"uppercase" => Helper::Uppercase,
+			"level_style" => Helper::LevelStyle,
+			"fixed_size" => Helper::FixedSize,
+			"min_size" => Helper::MinSize,
+			_ => return None,
+		})
+	}
+
+	/// Number of numeric params before the text param.
+	fn number_params(self) -> usize {
+		match self {
+			Helper::ColorRgb => 3,
+			Helper::FixedSize | Helper::MinSize => 1,
+			_ => 0,
+		}
+	}
+
+	fn call(self, numbers: &[u64], text: &str) -> String {
+		match self {
+			Helper::Bold => helpers::bold(text),
+			Helper::Cyan => helpers::cyan(text),
+			Helper::Yellow => helpers::yellow(text),
+			Helper::Red => helpers::red(text),
+			Helper::Blue => helpers::blue(text),
+			Helper::Purple => helpers::purple(text),
+			Helper::Green => helpers::green(text),
+			Helper::ColorRgb => helpers::color_rgb(numbers[0], numbers[1], numbers[2], text),
+			Helper::Uppercase => helpers::uppercase(text),
+			Helper::LevelStyle => helpers::level_style(text),
+			Helper::FixedSize => helpers::fixed_size(numbers[0], text),
+			Helper::MinSize => helpers::min_size(numbers[0], text),
+		}
+	}
+}
+
+/// The result of an expression.
+enum Evaluated<'a> {
+	Missing,
+	Field(Field<'a>),
+	Literal(&'a Value),
+	Rendered(String),
+}
+
+impl Evaluated<'_> {
+	fn as_str(&self) -> Option<&str> {
+		match self {
+			Evaluated::Field(Field::Text(text)) => Some(text),
+			Evaluated::Field(Field::Json(Value::String(text))) | Evaluated::Literal(Value::String(text)) => Some(text),
+			Evaluated::Rendered(text) => Some(text),
+			_ => None,
+		}
+	}
+
+	fn as_u64(&self) -> Option<u64> {
+		match self {
+			Evaluated::Field(Field::Json(value)) | Evaluated::Literal(value) => value.as_u64(),
+			_ => None,
+		}
+	}
+
+	fn is_truthy(&self, include_zero: bool) -> bool {
+		match self {
+			Evaluated::Missing => false,
+			Evaluated::Field(Field::Text(text)) => !text.is_empty(),
+			Evaluated::Field(Field::Json(value)) | Evaluated::Literal(value) => value.is_truthy(include_zero),
+			Evaluated::Rendered(text) => !text.is_empty(),
+		}
+	}
+}
+
+impl CompiledTemplate {
+	/// Compiles a template parsed by handlebars. Returns `None` for templates using other features.
+	pub fn compile(template: &Template) -> Option<CompiledTemplate> {
+		Some(CompiledTemplate {
+			elements: compile_elements(template)?,
+		})
+	}
+
+	pub fn render(&self, fields: &dyn Fields) -> Option<String> {
+		let mut rendered = String::new();
+		render_elements(&self.elements, fields, &mut rendered)?;
+		Some(rendered)
+	}
+}
+
+fn compile_elements(template: &Template) -> Option<Vec<Element>> {
+	let mut elements = Vec::with_capacity(template.elements.len());
+	for element in &template.elements {
+		match element {
+			TemplateElement::RawString(raw) => elements.push(Element::Raw(raw.clone())),
+			TemplateElement::Comment(_) => (),
+			TemplateElement::Expression(helper_template) | TemplateElement::HtmlExpression(helper_template) => {
+				if helper_template.block || helper_template.chain || !helper_template.hash.is_empty() || helper_template.block_param.is_some() {
+					return None;
+				}
+				if helper_template.params.is_empty() {
+					let name = field_name(&helper_template.name)?;
+					if Helper::from_name(name).is_some() || BUILTIN_HELPERS.contains(&name) {
+						return None;
+					}
+					elements.push(Element::Field(name.to_string()));
+				} else {
+					elements.push(Element::Call(compile_call(helper_name(&helper_template.name)?, &helper_template.params)?));
+				}
+			}
+			TemplateElement::HelperBlock(helper_template) => {
+				if helper_name(&helper_template.name)? != "if" || helper_template.chain || helper_template.block_param.is_some() || helper_template.params.len() != 1 {
+					return None;
+				}
+				let include_zero = match helper_template.hash.get("includeZero") {
+					None => false,
+					Some(Parameter::Literal(Value::Bool(include_zero))) => *include_zero,
+					Some(_) => return None,
+				};
+				if helper_template.hash.keys().any(|key| key != "includeZero") {
+					return None;
+				}
+				elements.push(Element::If {
+					condition: compile_expression(&helper_template.params[0])?,
+					include_zero,
+					then: match &helper_template.template {
+						Some(template) => compile_elements(template)?,
+						None => vec![],
+					},
+					otherwise: match &helper_template.inverse {
+						Some(template) => compile_elements(template)?,
+						None => vec![],
+					},
+				});
+			}
+			_ => return None,
+		}
+	}
+	Some(elements)
+}
+
+fn compile_call(name: &str, params: &[Parameter]) -> Option<Call> {
+	let helper = Helper::from_name(name)?;
+	let (text, numbers) = params.split_last()?;
+	if numbers.len() != helper.number_params() {
+		return None;
+	}
+	Some(Call {
+		helper,
+		numbers: numbers.iter().map(compile_expression).collect::<Option<_>>()?,
+		text: Box::new(compile_expression(text)?),
+	})
+}
+
+fn compile_expression(param: &Parameter) -> Option<Expression> {
+	match param {
+		Parameter::Path(_) => Some(Expression::Field(field_name(param)?.to_string())),
+		Parameter::Literal(value) => Some(Expression::Literal(value.clone())),
+		Parameter::Subexpression(subexpression) => match subexpression.as_element() {
+			TemplateElement::Expression(helper_template) if helper_template.hash.is_empty() => {
+				Some(Expression::Call(compile_call(helper_name(&helper_template.name)?, &helper_template.params)?))
+			}
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+fn helper_name(name: &Parameter) -> Option<&str> {
+	match name {
+		Parameter::Name(name) => Some(name),
+		_ => field_name(name),
+	}
+}
+
+/// Only top-level fields like `fblog_message` are supported, no paths like `nested.key` or `this`.
+fn field_name(name: &Parameter) -> Option<&str> {
+	match name {
+		Parameter::Path(Path::Relative((segments, raw))) => match segments.as_slice() {
+			[PathSeg::Named(name)] if name == raw => Some(name),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+fn render_elements(elements: &[Element], fields: &dyn Fields, rendered: &mut String) -> Option<()> {
+	for element in elements {
+		match element {
+			Element::Raw(raw) => rendered.push_str(raw),
+			Element::Field(name) => match fields.field(name) {
+				Some(Field::Text(text)) => rendered.push_str(text),
+				Some(Field::Json(Value::String(text))) => rendered.push_str(text),
+				Some(Field::Json(value)) => rendered.push_str(&value.render()),
+				None => (),
+			},
+			Element::Call(call) => rendered.push_str(&evaluate_call(call, fields)?),
+			Element::If {
+				condition,
+				include_zero,
+				then,
+				otherwise,
+			} => {
+				let branch = if evaluate(condition, fields)?.is_truthy(*include_zero) {
+					then
+				} else {
+					otherwise
+				};
+				render_elements(branch, fields, rendered)?;
+			}
+		}
+	}
+	Some(())
+}
+
+fn evaluate<'a>(expression: &'a Expression, fields: &'a dyn Fields) -> Option<Evaluated<'a>> {
+	Some(match expression {
+		Expression::Field(name) => fields.field(name).map(Evaluated::Field).unwrap_or(Evaluated::Missing),
+		Expression::Literal(value) => Evaluated::Literal(value),
+		Expression::Call(call) => Evaluated::Rendered(evaluate_call(call, fields)?),
+	})
+}
+
+fn evaluate_call(call: &Call, fields: &dyn Fields) -> Option<String> {
+	let mut numbers = [0u64; 3];
+	for (number, expression) in numbers.iter_mut().zip(&call.numbers) {
+		*number = evaluate(expression, fields)?.as_u64()?;
+	}
+	let text = evaluate(&call.text, fields)?;
+	Some(call.helper.call(&numbers, text.as_str()?))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::substitution::Substitution;
+	use crate::template;
+	use serde_json::{Map, json};
+
+	struct JsonFields(Map<String, Value>);
+
+	impl Fields for JsonFields {
+		fn field(&self, name: &str) -> Option<Field<'_>> {
+			self.0.get(name).map(Field::Json)
+		}
+	}
+
+	/// Renders with the compiled template and with handlebars, the compiled template must render the same or nothing.
+	fn render_both(format: &str, data: Value) -> (Option<String>, String) {
+		let handlebars = template::fblog_handlebar_registry(format.to_string(), String::new(), Substitution::default());
+		let compiled = CompiledTemplate::compile(handlebars.get_template("main_line").unwrap());
+		let expected = handlebars.render("main_line", &data).unwrap_or_else(|e| format!("error: {e}"));
+		let Value::Object(data) = data else { panic!("not an object") };
+		(compiled.and_then(|compiled| compiled.render(&JsonFields(data))), expected)
+	}
+
+	fn assert_same(format: &str, data: Value) {
+		let (rendered, expected) = render_both(format, data);
+		assert_eq!(rendered.as_ref(), Some(&expected), "{format}");
+	}
+
+	fn assert_fallback(format: &str, data: Value) {
+		let (rendered, _) = render_both(format, data);
+		assert_eq!(rendered, None, "{format}");
+	}
+
+	#[test]
+	fn default_formats() {
+		let entry = json!({
+			"fblog_timestamp": "2017-07-06T15:21:16",
+			"fblog_level": "info",
+			"fblog_message": "something happened",
+			"fblog_prefix": "pod/api",
+			"fblog_source_tag": "api.log",
+		});
+		assert_same(template::DEFAULT_MAIN_LINE_FORMAT, entry);
+		assert_same(
+			template::DEFAULT_MAIN_LINE_FORMAT,
+			json!({"fblog_timestamp": "", "fblog_level": "warning", "fblog_message": "", "fblog_prefix": ""}),
+		);
+		assert_same(template::DEFAULT_ADDITIONAL_VALUE_FORMAT, json!({"key": "nested > key", "value": "value"}));
+	}
+
+	#[test]
+	fn values_and_helpers() {
+		let entry = json!({"a": "text", "n": 42, "f": 1.5, "b": false, "null": null, "array": [1, "x", {"y": 1}], "object": {"y": 1}});
+		for format in [
+			"{{a}} {{n}} {{f}} {{b}} {{null}} {{missing}} {{array}} {{object}}",
+			"{{{a}}}{{! comment }} {{~a~}} {{a}}",
+			r#"{{color_rgb 10 20 30 "literal"}} {{min_size 8 (uppercase a)}} {{fixed_size 2 a}}"#,
+			"{{fixed_size n a}} {{red a}}{{green a}}{{blue a}}{{yellow a}}{{purple a}}{{cyan a}}{{level_style a}}",
+			"{{#if a}}yes{{else}}no{{/if}} {{#if missing}}yes{{else}}no{{/if}} {{#if (uppercase a)}}call{{/if}}",
+			"{{#if b}}b{{/if}}{{#if null}}null{{/if}}{{#if array}}array{{/if}}{{#if n}}n{{/if}}",
+		] {
+			assert_same(format, entry.clone());
+		}
+		assert_same(
+			"{{#if zero}}zero{{else}}none{{/if}} {{#if zero includeZero=true}}zero{{/if}}",
+			json!({"zero": 0}),
+		);
+	}
+
+	#[test]
+	fn fallback_to_handlebars() {
+		let entry = json!({"a": "text", "n": 42, "nested": {"key": "value"}});
+		for format in [
+			"{{nested.key}}",
+			"{{bold n}}",
+			"{{bold missing}}",
+			"{{fixed_size a n}}",
+			"{{#each nested}}{{this}}{{/each}}",
+			"{{#if a}}a{{else if n}}n{{/if}}",
+			"{{lookup nested \"key\"}}",
+			"{{len a}}",
+			"{{bold}}",
+			"{{substitute a}}",
+			"{{substitute}}",
+		] {
+			assert_fallback(format, entry.clone());
+		}
+	}
+}
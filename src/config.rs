@@ -2,6 +2,7 @@ use std::{collections::BTreeMap, fs, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use crate::preset::Preset;
 use crate::template::{DEFAULT_ADDITIONAL_VALUE_FORMAT, DEFAULT_MAIN_LINE_FORMAT};
 
 fn default_message_keys() -> Vec<String> {
@@ -20,6 +21,33 @@ fn default_always_print_fields() -> Vec<String> {
 	vec![]
 }
 
+fn default_stack_trace_keys() -> Vec<String> {
+	["stack", "exception", "error.stack_trace", "error > stack_trace", "err > stack", "@x"]
+		.map(String::from)
+		.to_vec()
+}
+
+/// Internals of the JDK, .NET, Node and common frameworks.
+fn default_collapsed_packages() -> Vec<String> {
+	[
+		"java.",
+		"javax.",
+		"jdk.",
+		"sun.",
+		"com.sun.",
+		"jakarta.",
+		"kotlin.",
+		"org.springframework.",
+		"org.apache.catalina.",
+		"org.apache.tomcat.",
+		"System.",
+		"Microsoft.",
+		"node:internal",
+	]
+	.map(String::from)
+	.to_vec()
+}
+
 fn default_level_keys() -> Vec<String> {
 	vec!["level".to_string(), "severity".to_string(), "log.level".to_string(), "loglevel".to_string()]
 }
@@ -28,6 +56,51 @@ fn default_level_map() -> BTreeMap<String, String> {
 	BTreeMap::from([])
 }
 
+fn default_level_order() -> Vec<String> {
+	["trace", "debug", "info", "warn", "error", "fatal"].map(String::from).to_vec()
+}
+
+/// Common level names and the numeric levels of bunyan/pino (10-60) and syslog (0-7).
+fn default_level_aliases() -> BTreeMap<String, String> {
+	[
+		("verbose", "trace"),
+		("information", "info"),
+		("notice", "info"),
+		("warning", "warn"),
+		("err", "error"),
+		("crit", "fatal"),
+		("critical", "fatal"),
+		("alert", "fatal"),
+		("emerg", "fatal"),
+		("panic", "fatal"),
+		("10", "trace"),
+		("20", "debug"),
+		("30", "info"),
+		("40", "warn"),
+		("50", "error"),
+		("60", "fatal"),
+		("0", "fatal"),
+		("1", "fatal"),
+		("2", "fatal"),
+		("3", "error"),
+		("4", "warn"),
+		("5", "info"),
+		("6", "info"),
+		("7", "debug"),
+	]
+	.into_iter()
+	.map(|(alias, level)| (alias.to_string(), level.to_string()))
+	.collect()
+}
+
+fn default_parse_patterns() -> BTreeMap<String, String> {
+	BTreeMap::from([])
+}
+
+fn default_presets() -> BTreeMap<String, Preset> {
+	BTreeMap::from([])
+}
+
 fn default_main_line_format() -> String {
 	DEFAULT_MAIN_LINE_FORMAT.to_string()
 }
@@ -50,12 +123,30 @@ pub struct Config {
 	#[serde(default = "default_always_print_fields")]
 	pub always_print_fields: Vec<String>,
 
+	#[serde(default = "default_stack_trace_keys")]
+	pub stack_trace_keys: Vec<String>,
+
+	#[serde(default = "default_collapsed_packages")]
+	pub collapsed_packages: Vec<String>,
+
 	#[serde(default = "default_level_keys")]
 	pub level_keys: Vec<String>,
 
 	#[serde(default = "default_level_map")]
 	pub level_map: BTreeMap<String, String>,
 
+	#[serde(default = "default_level_order")]
+	pub level_order: Vec<String>,
+
+	#[serde(default = "default_level_aliases")]
+	pub level_aliases: BTreeMap<String, String>,
+
+	#[serde(default = "default_parse_patterns")]
+	pub parse_patterns: BTreeMap<String, String>,
+
+	#[serde(default = "default_presets")]
+	pub presets: BTreeMap<String, Preset>,
+
 	#[serde(default = "default_main_line_format")]
 	pub main_line_format: String,
 
@@ -91,10 +182,16 @@ impl Config {
 			time_keys: default_time_keys(),
 			level_keys: default_level_keys(),
 			level_map: default_level_map(),
+			level_order: default_level_order(),
+			level_aliases: default_level_aliases(),
+			parse_patterns: default_parse_patterns(),
+			presets: default_presets(),
 			main_line_format: default_main_line_format(),
 			additional_value_format: default_additional_value_format(),
 			dump_all_exclude: default_dump_all_exclude(),
 			always_print_fields: default_always_print_fields(),
+			stack_trace_keys: default_stack_trace_keys(),
+			collapsed_packages: default_collapsed_packages(),
 		}
 	}
 
@@ -108,6 +205,7 @@ mod tests {
 	use std::fs;
 
 	use super::*;
+	use crate::time::EpochUnit;
 
 	#[test]
 	fn read_defaults_from_empty_config() {
@@ -119,8 +217,14 @@ mod tests {
 
 		assert_eq!(config.level_keys, default_level_keys());
 		assert_eq!(config.level_map, default_level_map());
+		assert_eq!(config.level_order, default_level_order());
+		assert_eq!(config.level_aliases, default_level_aliases());
+		assert_eq!(config.parse_patterns, default_parse_patterns());
+		assert_eq!(config.presets, default_presets());
 		assert_eq!(config.time_keys, default_time_keys());
 		assert_eq!(config.message_keys, default_message_keys());
+		assert_eq!(config.stack_trace_keys, default_stack_trace_keys());
+		assert_eq!(config.collapsed_packages, default_collapsed_packages());
 		assert_eq!(config.main_line_format, DEFAULT_MAIN_LINE_FORMAT);
 		assert_eq!(config.additional_value_format, DEFAULT_ADDITIONAL_VALUE_FORMAT);
 
@@ -151,4 +255,53 @@ mod tests {
 		.unwrap();
 		assert_eq!(config.level_map, BTreeMap::from([("10".to_string(), "trace".to_string()),]));
 	}
+
+	#[test]
+	fn read_level_order() {
+		let config: Config = toml::from_str(
+			r#"
+    level_order = ["debug", "info", "notice", "warn", "error"]
+
+    [level_aliases]
+    warning = "warn"
+    "#,
+		)
+		.unwrap();
+		assert_eq!(config.level_order, vec!["debug", "info", "notice", "warn", "error"]);
+		assert_eq!(config.level_aliases, BTreeMap::from([("warning".to_string(), "warn".to_string())]));
+	}
+
+	#[test]
+	fn read_presets() {
+		let config: Config = toml::from_str(
+			r#"
+    [presets.pino]
+    message_keys = ["message"]
+
+    [presets.service]
+    time_keys = ["ts"]
+    epoch_unit = "microseconds"
+    excluded_values = ["pid"]
+    "#,
+		)
+		.unwrap();
+		assert_eq!(config.presets["pino"].message_keys, Some(vec!["message".to_string()]));
+		assert_eq!(config.presets["pino"].time_keys, None);
+		assert_eq!(config.presets["service"].epoch_unit, Some(EpochUnit::Microseconds));
+	}
+
+	#[test]
+	fn read_parse_patterns() {
+		let config: Config = toml::from_str(
+			r#"
+    [parse_patterns]
+    nginx_error = '^(?P<time>\S+ \S+) \[(?P<level>\w+)\] (?P<message>.*)$'
+    "#,
+		)
+		.unwrap();
+		assert_eq!(
+			config.parse_patterns,
+			BTreeMap::from([("nginx_error".to_string(), r"^(?P<time>\S+ \S+) \[(?P<level>\w+)\] (?P<message>.*)$".to_string()),])
+		);
+	}
 }
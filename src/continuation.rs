@@ -0,0 +1,174 @@
+use crate::input::InputLine;
+use crate::log_settings::LogSettings;
+use crate::process;
+use regex::Regex;
+use std::io;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+/// The field continuation lines are added as, visible to templates and filters.
+pub const KEY: &str = "fblog_continuation";
+/// Indented lines and the lines of Java and Python stack traces.
+pub const DEFAULT_PATTERN: &str = r"^(\s|at |Caused by|Traceback)";
+/// How long a line of an idle input waits for its continuation lines, so the last line is printed while waiting for input.
+const FLUSH_TIMEOUT: Duration = Duration::from_millis(100);
+
+type Line = (usize, io::Result<InputLine>);
+
+/// Joins lines that continue the previous line of the same input, like the lines of a stack trace written after a json
+/// log entry. The continuation lines are appended to the line separated by newlines, read lines never contain one.
+///
+/// The lines are received from a thread reading the input. A line is yielded when the next line is received. If the input
+/// can be idle (stdin or `--follow`) it is also yielded when no line is received within a short timeout.
+pub struct Continuations<'a> {
+	log_settings: &'a LogSettings,
+	continuation_regex: &'a Regex,
+	lines: Receiver<Line>,
+	next_line: Option<Line>,
+	flush_timeout: Option<Duration>,
+}
+
+impl<'a> Continuations<'a> {
+	pub fn new(log_settings: &'a LogSettings, continuation_regex: &'a Regex, lines: Receiver<Line>) -> Continuations<'a> {
+		Continuations {
+			log_settings,
+			continuation_regex,
+			lines,
+			next_line: None,
+			flush_timeout: log_settings.idle_input.then_some(FLUSH_TIMEOUT),
+		}
+	}
+}
+
+impl Iterator for Continuations<'_> {
+	type Item = Line;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (index, line) = match self.next_line.take() {
+			Some(next_line) => next_line,
+			None => self.lines.recv().ok()?,
+		};
+		let Ok(mut line) = line else {
+			return Some((index, line));
+		};
+		loop {
+			let next_line = match self.flush_timeout {
+				Some(flush_timeout) => self.lines.recv_timeout(flush_timeout).ok(),
+				None => self.lines.recv().ok(),
+			};
+			match next_line {
+				None => break,
+				Some((next_index, Ok(next_line))) if next_index == index && is_continuation(self.log_settings, self.continuation_regex, &next_line) => {
+					line.text.push('\n');
+					line.text.push_str(&next_line.text);
+				}
+				Some(next_line) => {
+					self.next_line = Some(next_line);
+					break;
+				}
+			}
+		}
+		Some((index, Ok(line)))
+	}
+}
+
+/// Lines matching the pattern are only continuations if they are no log entries, e.g. indented json.
+fn is_continuation(log_settings: &LogSettings, continuation_regex: &Regex, line: &InputLine) -> bool {
+	continuation_regex.is_match(&line.text)
+		&& match &line.parsed {
+			Some(parsed_line) => parsed_line.is_none(),
+			None => process::parse_log_entries(log_settings, &line.text).is_none(),
+		}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::mpsc;
+
+	fn read_line(text: &str) -> Line {
+		(0, Ok(InputLine::new(text.to_string())))
+	}
+
+	fn join(log_settings: &LogSettings, lines: &[(usize, &str)]) -> Vec<(usize, String)> {
+		let (line_sender, line_receiver) = mpsc::channel();
+		for (index, line) in lines {
+			line_sender.send((*index, Ok(InputLine::new(line.to_string())))).unwrap();
+		}
+		drop(line_sender);
+		let continuation_regex = Regex::new(DEFAULT_PATTERN).unwrap();
+		Continuations::new(log_settings, &continuation_regex, line_receiver)
+			.map(|(index, line)| (index, line.unwrap().text))
+			.collect()
+	}
+
+	#[test]
+	fn join_continuation_lines() {
+		let log_settings = LogSettings::new_default_settings();
+		let lines = [
+			(0, "continued without a previous line"),
+			(0, r#"{"message": "failed"}"#),
+			(0, "java.lang.IllegalStateException: boom"),
+			(0, "\tat com.acme.Service.save(Service.java:42)"),
+			(0, "Caused by: java.io.IOException: disk full"),
+			(1, "  at another input"),
+			(0, r#"  {"message": "indented json"}"#),
+			(0, "Traceback (most recent call last):"),
+		];
+		assert_eq!(
+			join(&log_settings, &lines),
+			vec![
+				(0, "continued without a previous line".to_string()),
+				(0, r#"{"message": "failed"}"#.to_string()),
+				(
+					0,
+					"java.lang.IllegalStateException: boom\n\tat com.acme.Service.save(Service.java:42)\nCaused by: java.io.IOException: disk full".to_string()
+				),
+				(1, "  at another input".to_string()),
+				(0, "  {\"message\": \"indented json\"}\nTraceback (most recent call last):".to_string()),
+			]
+		);
+	}
+
+	#[test]
+	fn yield_line_while_input_is_idle() {
+		let mut log_settings = LogSettings::new_default_settings();
+		log_settings.idle_input = true;
+		let continuation_regex = Regex::new(DEFAULT_PATTERN).unwrap();
+		let (line_sender, line_receiver) = mpsc::channel();
+		let mut continuations = Continuations::new(&log_settings, &continuation_regex, line_receiver);
+
+		line_sender.send(read_line("java.lang.IllegalStateException: boom")).unwrap();
+		line_sender.send(read_line("\tat com.acme.Service.save(Service.java:42)")).unwrap();
+		let (_, line) = continuations.next().unwrap();
+		assert_eq!(
+			line.unwrap().text,
+			"java.lang.IllegalStateException: boom\n\tat com.acme.Service.save(Service.java:42)"
+		);
+
+		line_sender.send(read_line("\tat com.acme.Controller.post(Controller.java:7)")).unwrap();
+		let (_, line) = continuations.next().unwrap();
+		assert_eq!(line.unwrap().text, "\tat com.acme.Controller.post(Controller.java:7)");
+	}
+
+	#[test]
+	fn wait_for_continuation_of_file() {
+		let log_settings = LogSettings::new_default_settings();
+		let continuation_regex = Regex::new(DEFAULT_PATTERN).unwrap();
+		let (line_sender, line_receiver) = mpsc::channel();
+		let mut continuations = Continuations::new(&log_settings, &continuation_regex, line_receiver);
+
+		line_sender.send(read_line("java.lang.IllegalStateException: boom")).unwrap();
+		let slow_reader = std::thread::spawn(move || {
+			std::thread::sleep(FLUSH_TIMEOUT * 2);
+			line_sender.send(read_line("\tat com.acme.Service.save(Service.java:42)")).unwrap();
+		});
+		let (_, line) = continuations.next().unwrap();
+		assert_eq!(
+			line.unwrap().text,
+			"java.lang.IllegalStateException: boom\n\tat com.acme.Service.save(Service.java:42)"
+		);
+		slow_reader.join().unwrap();
+		assert!(continuations.next().is_none());
+	}
+}
@@ -0,0 +1,141 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io;
+
+lazy_static! {
+	static ref CRI_LINE: Regex = Regex::new(r"^(\d{4}-\d{2}-\d{2}T\S+) (stdout|stderr) ([PF])(?: (.*))?$").unwrap();
+}
+
+/// A line of the kubernetes CRI container log format: `2024-05-21T11:21:29.536Z stdout F {"msg":...}`.
+pub struct CriLine<'a> {
+	pub timestamp: &'a str,
+	pub stream: &'a str,
+	pub partial: bool,
+	pub content: &'a str,
+}
+
+pub fn parse(line: &str) -> Option<CriLine<'_>> {
+	let captures = CRI_LINE.captures(line)?;
+	Some(CriLine {
+		timestamp: captures.get(1)?.as_str(),
+		stream: captures.get(2)?.as_str(),
+		partial: &captures[3] == "P",
+		content: captures.get(4).map(|content| content.as_str()).unwrap_or(""),
+	})
+}
+
+/// Joins partial CRI lines (tag `P`) with the following lines of the same stream into one full line (tag `F`).
+/// All other lines are passed through unchanged.
+pub struct CriLines<I> {
+	lines: I,
+	partial_stdout: Option<(String, String)>,
+	partial_stderr: Option<(String, String)>,
+}
+
+impl<I> CriLines<I> {
+	pub fn new(lines: I) -> CriLines<I> {
+		CriLines {
+			lines,
+			partial_stdout: None,
+			partial_stderr: None,
+		}
+	}
+
+	fn partial(&mut self, stream: &str) -> &mut Option<(String, String)> {
+		if stream == "stderr" {
+			&mut self.partial_stderr
+		} else {
+			&mut self.partial_stdout
+		}
+	}
+}
+
+fn full_line(timestamp: &str, stream: &str, content: &str) -> String {
+	format!("{timestamp} {stream} F {content}")
+}
+
+impl<I: Iterator<Item = io::Result<String>>> Iterator for CriLines<I> {
+	type Item = io::Result<String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let Some(line) = self.lines.next() else {
+				// Flush partial lines that were never finished.
+				let (stream, (timestamp, content)) = match self.partial_stdout.take() {
+					Some(partial) => ("stdout", partial),
+					None => ("stderr", self.partial_stderr.take()?),
+				};
+				return Some(Ok(full_line(&timestamp, stream, &content)));
+			};
+
+			let Ok(read_line) = &line else {
+				return Some(line);
+			};
+			let Some(cri_line) = parse(read_line) else {
+				return Some(line);
+			};
+
+			let (timestamp, stream, partial, content) = (cri_line.timestamp, cri_line.stream, cri_line.partial, cri_line.content);
+			let pending = self.partial(stream);
+			match (pending.take(), partial) {
+				(None, false) => return Some(line),
+				(None, true) => *pending = Some((timestamp.to_string(), content.to_string())),
+				(Some((first_timestamp, mut joined)), true) => {
+					joined.push_str(content);
+					*pending = Some((first_timestamp, joined));
+				}
+				(Some((first_timestamp, mut joined)), false) => {
+					joined.push_str(content);
+					return Some(Ok(full_line(&first_timestamp, stream, &joined)));
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn join(lines: &[&str]) -> Vec<String> {
+		CriLines::new(lines.iter().map(|line| Ok(line.to_string()))).map(Result::unwrap).collect()
+	}
+
+	#[test]
+	fn parse_cri_line() {
+		let cri_line = parse(r#"2024-05-21T11:21:29.536Z stderr F {"msg": "x"}"#).unwrap();
+		assert_eq!(cri_line.timestamp, "2024-05-21T11:21:29.536Z");
+		assert_eq!(cri_line.stream, "stderr");
+		assert!(!cri_line.partial);
+		assert_eq!(cri_line.content, r#"{"msg": "x"}"#);
+
+		assert!(parse(r#"{"msg": "x"}"#).is_none());
+		assert!(parse("2024-05-21T11:21:29.536Z stdout P").unwrap().partial);
+	}
+
+	#[test]
+	fn join_partial_lines() {
+		assert_eq!(
+			join(&[
+				r#"2024-05-21T11:21:29.536Z stdout P {"msg": "#,
+				r#"2024-05-21T11:21:29.537Z stderr F error"#,
+				r#"2024-05-21T11:21:29.538Z stdout P "a long "#,
+				r#"2024-05-21T11:21:29.539Z stdout F line"}"#,
+				"no cri line",
+			]),
+			vec![
+				"2024-05-21T11:21:29.537Z stderr F error",
+				r#"2024-05-21T11:21:29.536Z stdout F {"msg": "a long line"}"#,
+				"no cri line",
+			]
+		);
+	}
+
+	#[test]
+	fn flush_unfinished_partial_line() {
+		assert_eq!(
+			join(&["2024-05-21T11:21:29.536Z stdout P unfinished"]),
+			vec!["2024-05-21T11:21:29.536Z stdout F unfinished"]
+		);
+	}
+}
@@ -0,0 +1,138 @@
+use flate2::read::MultiGzDecoder;
+use std::io::{self, Cursor, Read};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// bzip2 is only detected with the block size `1` to `9` after its magic, text could start with `BZh` as well.
+const MAGICS: &[&[u8]] = &[
+	GZIP_MAGIC, ZSTD_MAGIC, b"BZh1", b"BZh2", b"BZh3", b"BZh4", b"BZh5", b"BZh6", b"BZh7", b"BZh8", b"BZh9", XZ_MAGIC,
+];
+
+/// Detects compressed input by its magic bytes and decodes it while reading.
+/// Input that is not compressed is passed through unchanged.
+pub fn decompress(mut input: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+	let head = read_head(&mut input, MAGICS)?;
+	let compression = MAGICS
+		.iter()
+		.find(|magic| head.starts_with(magic))
+		.map(|magic| if magic.starts_with(BZIP2_MAGIC) { BZIP2_MAGIC } else { magic });
+	let input: Box<dyn Read + Send> = Box::new(Cursor::new(head).chain(input));
+
+	Ok(match compression {
+		Some(GZIP_MAGIC) => Box::new(FuseOnError::new(MultiGzDecoder::new(input))),
+		Some(ZSTD_MAGIC) => Box::new(FuseOnError::new(zstd::stream::read::Decoder::new(input)?)),
+		Some(BZIP2_MAGIC) => Box::new(FuseOnError::new(bzip2::read::MultiBzDecoder::new(input))),
+		Some(XZ_MAGIC) => Box::new(FuseOnError::new(liblzma::read::XzDecoder::new_multi_decoder(input))),
+		_ => input,
+	})
+}
+
+/// Reads just enough bytes to tell whether the input starts with one of the byte sequences, like magic bytes or a
+/// byte order mark. Stops as soon as none can match anymore, so reading a slow stream is not delayed.
+pub(crate) fn read_head(input: &mut dyn Read, sequences: &[&[u8]]) -> io::Result<Vec<u8>> {
+	let max_len = sequences.iter().map(|sequence| sequence.len()).max().unwrap_or(0);
+	let mut head = Vec::with_capacity(max_len);
+	let mut byte = [0u8; 1];
+	while sequences.iter().any(|sequence| sequence.starts_with(&head) && sequence.len() > head.len()) {
+		match input.read(&mut byte) {
+			Ok(0) => break,
+			Ok(_) => head.push(byte[0]),
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(head)
+}
+
+/// Reports a decoding error once and ends the input afterwards.
+/// Corrupt data would otherwise fail every following read and no line could ever be completed.
+struct FuseOnError<R> {
+	inner: R,
+	failed: bool,
+}
+
+impl<R> FuseOnError<R> {
+	fn new(inner: R) -> FuseOnError<R> {
+		FuseOnError { inner, failed: false }
+	}
+}
+
+impl<R: Read> Read for FuseOnError<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.failed {
+			return Ok(0);
+		}
+		self.inner.read(buf).inspect_err(|_| self.failed = true)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Write;
+
+	static LOG: &str = "{\"message\": \"first\"}\n{\"message\": \"second\"}\n";
+
+	fn decompress_to_string(data: Vec<u8>) -> String {
+		let mut out = String::new();
+		decompress(Box::new(Cursor::new(data))).unwrap().read_to_string(&mut out).unwrap();
+		out
+	}
+
+	#[test]
+	fn plain_input() {
+		assert_eq!(decompress_to_string(LOG.as_bytes().to_vec()), LOG);
+		assert_eq!(decompress_to_string(b"{}".to_vec()), "{}");
+		assert_eq!(decompress_to_string(vec![]), "");
+	}
+
+	#[test]
+	fn gzip_input() {
+		let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		encoder.write_all(LOG.as_bytes()).unwrap();
+		let mut data = encoder.finish().unwrap();
+		// concatenated members (e.g. appended with `gzip -c >>`) are read as well
+		data.extend(data.clone());
+		assert_eq!(decompress_to_string(data), format!("{LOG}{LOG}"));
+	}
+
+	#[test]
+	fn zstd_input() {
+		let data = zstd::stream::encode_all(LOG.as_bytes(), 0).unwrap();
+		assert_eq!(decompress_to_string(data), LOG);
+	}
+
+	#[test]
+	fn bzip2_input() {
+		let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+		encoder.write_all(LOG.as_bytes()).unwrap();
+		assert_eq!(decompress_to_string(encoder.finish().unwrap()), LOG);
+	}
+
+	#[test]
+	fn text_starting_like_bzip2() {
+		let text = "BZh is not bzip2\n{\"message\": \"second\"}\n";
+		assert_eq!(decompress_to_string(text.as_bytes().to_vec()), text);
+		assert_eq!(decompress_to_string(b"BZh".to_vec()), "BZh");
+	}
+
+	#[test]
+	fn xz_input() {
+		let mut encoder = liblzma::write::XzEncoder::new(Vec::new(), 6);
+		encoder.write_all(LOG.as_bytes()).unwrap();
+		assert_eq!(decompress_to_string(encoder.finish().unwrap()), LOG);
+	}
+
+	#[test]
+	fn corrupt_input() {
+		let mut data = GZIP_MAGIC.to_vec();
+		data.extend(b"not really gzip");
+		let mut input = decompress(Box::new(Cursor::new(data))).unwrap();
+		let mut buf = [0u8; 64];
+		assert!(input.read(&mut buf).is_err());
+		assert_eq!(input.read(&mut buf).unwrap(), 0);
+	}
+}
@@ -0,0 +1,89 @@
+use serde_json::{Map, Value};
+
+const DOCKER_LOG_KEY: &str = "log";
+const DOCKER_STREAM_KEY: &str = "stream";
+const DOCKER_TIME_KEY: &str = "time";
+
+/// Merges json objects embedded as string values into the log entry.
+///
+/// The values of the given keys and the `log` value of docker json-file envelopes
+/// (`{"log": "...", "stream": "stderr", "time": "..."}`) are parsed.
+/// Fields of the embedded object take precedence, the other envelope fields stay available.
+pub fn unwrap_embedded_json(log_entry: &mut Map<String, Value>, embedded_json_keys: &[String]) {
+	for key in embedded_json_keys {
+		merge_embedded_json(log_entry, key);
+	}
+
+	if is_docker_envelope(log_entry) && !merge_embedded_json(log_entry, DOCKER_LOG_KEY) && !log_entry.contains_key("message") {
+		// Plain text output of a container
+		if let Some(Value::String(log)) = log_entry.remove(DOCKER_LOG_KEY) {
+			log_entry.insert("message".to_string(), Value::String(log.trim_end().to_string()));
+		}
+	}
+}
+
+fn is_docker_envelope(log_entry: &Map<String, Value>) -> bool {
+	matches!(log_entry.get(DOCKER_LOG_KEY), Some(Value::String(_)))
+		&& matches!(log_entry.get(DOCKER_STREAM_KEY), Some(Value::String(_)))
+		&& log_entry.contains_key(DOCKER_TIME_KEY)
+}
+
+fn merge_embedded_json(log_entry: &mut Map<String, Value>, key: &str) -> bool {
+	let embedded = match log_entry.get(key) {
+		Some(Value::String(value)) => match serde_json::from_str::<Value>(value.trim_end()) {
+			Ok(Value::Object(embedded)) => embedded,
+			_ => return false,
+		},
+		_ => return false,
+	};
+	log_entry.remove(key);
+	log_entry.extend(embedded);
+	true
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn unwrapped(log_entry: Value, embedded_json_keys: &[&str]) -> Value {
+		let Value::Object(mut log_entry) = log_entry else { panic!("not an object") };
+		let keys: Vec<String> = embedded_json_keys.iter().map(ToString::to_string).collect();
+		unwrap_embedded_json(&mut log_entry, &keys);
+		Value::Object(log_entry)
+	}
+
+	#[test]
+	fn docker_envelope() {
+		assert_eq!(
+			unwrapped(
+				json!({"log": "{\"level\":\"info\",\"msg\":\"started\"}\n", "stream": "stderr", "time": "2024-05-21T11:21:29.536Z"}),
+				&[]
+			),
+			json!({"level": "info", "msg": "started", "stream": "stderr", "time": "2024-05-21T11:21:29.536Z"})
+		);
+	}
+
+	#[test]
+	fn docker_envelope_with_plain_text() {
+		assert_eq!(
+			unwrapped(
+				json!({"log": "listening on :8080\n", "stream": "stdout", "time": "2024-05-21T11:21:29.536Z"}),
+				&[]
+			),
+			json!({"message": "listening on :8080", "stream": "stdout", "time": "2024-05-21T11:21:29.536Z"})
+		);
+	}
+
+	#[test]
+	fn embedded_json_key() {
+		assert_eq!(
+			unwrapped(json!({"payload": "{\"msg\": \"inner\"}", "source": "queue"}), &["payload"]),
+			json!({"msg": "inner", "source": "queue"})
+		);
+		assert_eq!(
+			unwrapped(json!({"payload": "no json", "source": "queue"}), &["payload"]),
+			json!({"payload": "no json", "source": "queue"})
+		);
+	}
+}
@@ -0,0 +1,250 @@
+use crate::decompress::read_head;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Cursor, Read};
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16BE_BOM: &[u8] = &[0xfe, 0xff];
+
+const BOMS: &[&[u8]] = &[UTF8_BOM, UTF16LE_BOM, UTF16BE_BOM];
+
+/// How bytes that are no valid UTF-8 are decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+	/// Invalid bytes are shown escaped (`\xff`)
+	Utf8,
+	/// Lines that are no valid UTF-8 are decoded as Latin-1
+	Latin1,
+}
+
+impl Encoding {
+	pub fn from_name(name: &str) -> Option<Encoding> {
+		match name {
+			"utf-8" => Some(Encoding::Utf8),
+			"latin1" => Some(Encoding::Latin1),
+			_ => None,
+		}
+	}
+}
+
+/// Detects a byte order mark. UTF-16 input is converted to UTF-8 while reading, a UTF-8 byte order mark is removed.
+pub fn decode_input(mut input: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+	let head = read_head(&mut input, BOMS)?;
+	Ok(if head.starts_with(UTF8_BOM) {
+		input
+	} else if head.starts_with(UTF16LE_BOM) {
+		Box::new(Utf16Reader::new(Box::new(Cursor::new(head[UTF16LE_BOM.len()..].to_vec()).chain(input)), true))
+	} else if head.starts_with(UTF16BE_BOM) {
+		Box::new(Utf16Reader::new(Box::new(Cursor::new(head[UTF16BE_BOM.len()..].to_vec()).chain(input)), false))
+	} else {
+		Box::new(Cursor::new(head).chain(input))
+	})
+}
+
+/// Decodes a line. Valid UTF-8 is used as is, even if other lines of the input have a different encoding.
+pub fn decode_line(bytes: &[u8], encoding: Encoding) -> String {
+	match std::str::from_utf8(bytes) {
+		Ok(line) => line.to_string(),
+		Err(_) => match encoding {
+			Encoding::Latin1 => bytes.iter().map(|byte| char::from(*byte)).collect(),
+			Encoding::Utf8 => escape_invalid_bytes(bytes),
+		},
+	}
+}
+
+/// Replaces invalid bytes with `\xff`. Within json strings the backslash is escaped,
+/// so the json stays valid and the escaped byte is shown in the value.
+fn escape_invalid_bytes(bytes: &[u8]) -> String {
+	let mut decoded = String::with_capacity(bytes.len() + 16);
+	let mut in_string = false;
+	let mut escaped = false;
+	for chunk in bytes.utf8_chunks() {
+		for c in chunk.valid().chars() {
+			if escaped {
+				escaped = false;
+			} else if c == '\\' && in_string {
+				escaped = true;
+			} else if c == '"' {
+				in_string = !in_string;
+			}
+			decoded.push(c);
+		}
+		for byte in chunk.invalid() {
+			let backslash = if in_string { "\\\\" } else { "\\" };
+			write!(decoded, "{backslash}x{byte:02x}").expect("Should be able to write to string");
+			escaped = false;
+		}
+	}
+	decoded
+}
+
+/// Reads lines as bytes and decodes them, so invalid UTF-8 does not prevent reading the line.
+pub struct Lines<R> {
+	input: R,
+	encoding: Encoding,
+	buffer: Vec<u8>,
+}
+
+impl<R: BufRead> Lines<R> {
+	pub fn new(input: R, encoding: Encoding) -> Lines<R> {
+		Lines {
+			input,
+			encoding,
+			buffer: Vec::new(),
+		}
+	}
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+	type Item = io::Result<String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.buffer.clear();
+		match self.input.read_until(b'\n', &mut self.buffer) {
+			Ok(0) => None,
+			Ok(_) => {
+				let line = self.buffer.strip_suffix(b"\n").unwrap_or(&self.buffer);
+				let line = line.strip_suffix(b"\r").unwrap_or(line);
+				Some(Ok(decode_line(line, self.encoding)))
+			}
+			Err(e) => Some(Err(e)),
+		}
+	}
+}
+
+/// Converts UTF-16 input to UTF-8. Invalid code units are replaced with U+FFFD.
+struct Utf16Reader {
+	input: Box<dyn Read + Send>,
+	little_endian: bool,
+	raw: Vec<u8>,
+	decoded: Vec<u8>,
+	position: usize,
+	eof: bool,
+}
+
+impl Utf16Reader {
+	fn new(input: Box<dyn Read + Send>, little_endian: bool) -> Utf16Reader {
+		Utf16Reader {
+			input,
+			little_endian,
+			raw: Vec::new(),
+			decoded: Vec::new(),
+			position: 0,
+			eof: false,
+		}
+	}
+
+	fn fill(&mut self) -> io::Result<()> {
+		let mut chunk = [0u8; 8192];
+		let read = self.input.read(&mut chunk)?;
+		self.decoded.clear();
+		self.position = 0;
+
+		if read == 0 {
+			self.eof = true;
+			if !self.raw.is_empty() {
+				self.decoded.extend(char::REPLACEMENT_CHARACTER.to_string().as_bytes());
+				self.raw.clear();
+			}
+			return Ok(());
+		}
+
+		self.raw.extend(&chunk[..read]);
+		let mut units: Vec<u16> = self
+			.raw
+			.chunks_exact(2)
+			.map(|unit| {
+				if self.little_endian {
+					u16::from_le_bytes([unit[0], unit[1]])
+				} else {
+					u16::from_be_bytes([unit[0], unit[1]])
+				}
+			})
+			.collect();
+		// A high surrogate at the end needs the next code unit of the following chunk.
+		if units.last().is_some_and(|unit| (0xd800..0xdc00).contains(unit)) {
+			units.pop();
+		}
+		for c in char::decode_utf16(units.iter().copied()) {
+			let mut encoded = [0u8; 4];
+			self
+				.decoded
+				.extend(c.unwrap_or(char::REPLACEMENT_CHARACTER).encode_utf8(&mut encoded).as_bytes());
+		}
+		self.raw.drain(..units.len() * 2);
+		Ok(())
+	}
+}
+
+impl Read for Utf16Reader {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		while self.position >= self.decoded.len() {
+			if self.eof {
+				return Ok(0);
+			}
+			self.fill()?;
+		}
+		let len = buf.len().min(self.decoded.len() - self.position);
+		buf[..len].copy_from_slice(&self.decoded[self.position..self.position + len]);
+		self.position += len;
+		Ok(len)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn decode_to_string(data: Vec<u8>) -> String {
+		let mut out = String::new();
+		decode_input(Box::new(Cursor::new(data))).unwrap().read_to_string(&mut out).unwrap();
+		out
+	}
+
+	fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+		let mut data = if little_endian { UTF16LE_BOM.to_vec() } else { UTF16BE_BOM.to_vec() };
+		for unit in text.encode_utf16() {
+			data.extend(if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() });
+		}
+		data
+	}
+
+	#[test]
+	fn byte_order_marks() {
+		let text = "{\"message\": \"grüße 🦀\"}\n";
+		assert_eq!(decode_to_string(utf16(text, true)), text);
+		assert_eq!(decode_to_string(utf16(text, false)), text);
+
+		let mut utf8 = UTF8_BOM.to_vec();
+		utf8.extend(text.as_bytes());
+		assert_eq!(decode_to_string(utf8), text);
+		assert_eq!(decode_to_string(text.as_bytes().to_vec()), text);
+	}
+
+	#[test]
+	fn invalid_bytes_in_json_string() {
+		let line = decode_line(b"{\"message\": \"bad \xff byte\", \"level\": \"info\"}", Encoding::Utf8);
+		assert_eq!(line, r#"{"message": "bad \\xff byte", "level": "info"}"#);
+		let log_entry: serde_json::Value = serde_json::from_str(&line).unwrap();
+		assert_eq!(log_entry["message"], r"bad \xff byte");
+	}
+
+	#[test]
+	fn invalid_bytes_outside_json_string() {
+		assert_eq!(decode_line(b"plain \xe7\xda text", Encoding::Utf8), r"plain \xe7\xda text");
+		assert_eq!(decode_line(b"{\"a\": \"\\\"\xff\"}", Encoding::Utf8), r#"{"a": "\"\\xff"}"#);
+	}
+
+	#[test]
+	fn latin1_fallback() {
+		assert_eq!(decode_line(b"gr\xfc\xdfe", Encoding::Latin1), "grüße");
+		assert_eq!(decode_line("grüße".as_bytes(), Encoding::Latin1), "grüße");
+	}
+
+	#[test]
+	fn read_lines() {
+		let input = io::BufReader::new(&b"first\r\nsecond \xff\nthird"[..]);
+		let lines: Vec<String> = Lines::new(input, Encoding::Utf8).map(Result::unwrap).collect();
+		assert_eq!(lines, vec!["first", r"second \xff", "third"]);
+	}
+}
@@ -0,0 +1,152 @@
+use serde_json::{Map, Value};
+
+/// A log entry, or a value of a json array that is no log entry.
+pub type LogEntry = Result<Map<String, Value>, Value>;
+
+/// Turns a parsed json value into log entries.
+///
+/// Objects are log entries, arrays with objects are expanded into one log entry per object. Their other elements
+/// are kept in between as errors, so they can be printed as they are.
+/// With an explode path, the array at this path is expanded as well and every element
+/// gets the fields of its parent. Values that are no log entries are returned as error.
+pub fn into_log_entries(value: Value, maybe_explode_path: Option<&str>) -> Result<Vec<LogEntry>, Value> {
+	match value {
+		Value::Object(log_entry) => Ok(explode(log_entry, maybe_explode_path)),
+		Value::Array(values) if values.iter().any(Value::is_object) => Ok(
+			values
+				.into_iter()
+				.flat_map(|value| match value {
+					Value::Object(log_entry) => explode(log_entry, maybe_explode_path),
+					value => vec![Err(value)],
+				})
+				.collect(),
+		),
+		value => Err(value),
+	}
+}
+
+fn explode(log_entry: Map<String, Value>, maybe_explode_path: Option<&str>) -> Vec<LogEntry> {
+	let Some(explode_path) = maybe_explode_path else {
+		return vec![Ok(log_entry)];
+	};
+	let path: Vec<&str> = explode_path.split('.').collect();
+
+	let mut parent = log_entry;
+	match take_array(&mut parent, &path) {
+		Some(children) => children.into_iter().map(|child| Ok(merge_child(&parent, &path, child))).collect(),
+		None => vec![Ok(parent)],
+	}
+}
+
+fn take_array(object: &mut Map<String, Value>, path: &[&str]) -> Option<Vec<Value>> {
+	match path {
+		[] => None,
+		[key] => match object.get(*key) {
+			Some(Value::Array(_)) => match object.remove(*key) {
+				Some(Value::Array(values)) => Some(values),
+				_ => None,
+			},
+			_ => None,
+		},
+		[key, rest @ ..] => match object.get_mut(*key) {
+			Some(Value::Object(nested)) => take_array(nested, rest),
+			_ => None,
+		},
+	}
+}
+
+/// Copies the parent fields into a child entry. Fields of object children take precedence over the parent fields,
+/// other children replace the array in the copy of the parent.
+fn merge_child(parent: &Map<String, Value>, path: &[&str], child: Value) -> Map<String, Value> {
+	let mut log_entry = parent.clone();
+	match child {
+		Value::Object(child_fields) => log_entry.extend(child_fields),
+		child => {
+			let (key, nested_path) = path.split_last().expect("path is not empty");
+			let mut target = &mut log_entry;
+			for nested_key in nested_path {
+				target = match target.get_mut(*nested_key) {
+					Some(Value::Object(nested)) => nested,
+					_ => unreachable!("path was resolved before"),
+				};
+			}
+			target.insert(key.to_string(), child);
+		}
+	}
+	log_entry
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn entries(value: Value, maybe_explode_path: Option<&str>) -> Vec<Value> {
+		into_log_entries(value, maybe_explode_path)
+			.unwrap()
+			.into_iter()
+			.map(|log_entry| log_entry.map(Value::Object).unwrap())
+			.collect()
+	}
+
+	#[test]
+	fn single_entry() {
+		assert_eq!(entries(json!({"message": "a"}), None), vec![json!({"message": "a"})]);
+	}
+
+	#[test]
+	fn top_level_array() {
+		assert_eq!(
+			entries(json!([{"message": "a"}, {"message": "b"}]), None),
+			vec![json!({"message": "a"}), json!({"message": "b"})]
+		);
+	}
+
+	#[test]
+	fn array_with_other_values() {
+		assert_eq!(
+			into_log_entries(json!([1, {"message": "a"}, "b"]), None).unwrap(),
+			vec![Err(json!(1)), Ok(Map::from_iter([("message".to_string(), json!("a"))])), Err(json!("b"))]
+		);
+	}
+
+	#[test]
+	fn no_log_entries() {
+		assert!(into_log_entries(json!([]), None).is_err());
+		assert!(into_log_entries(json!([1, "a"]), None).is_err());
+		assert!(into_log_entries(json!("message"), None).is_err());
+	}
+
+	#[test]
+	fn explode_array() {
+		assert_eq!(
+			entries(
+				json!({"host": "a", "events": [{"message": "b"}, {"message": "c", "host": "d"}]}),
+				Some("events")
+			),
+			vec![json!({"host": "a", "message": "b"}), json!({"host": "d", "message": "c"})]
+		);
+	}
+
+	#[test]
+	fn explode_nested_array() {
+		assert_eq!(
+			entries(
+				json!({"host": "a", "payload": {"id": 1, "events": [{"message": "b"}, "c"]}}),
+				Some("payload.events")
+			),
+			vec![
+				json!({"host": "a", "payload": {"id": 1}, "message": "b"}),
+				json!({"host": "a", "payload": {"id": 1, "events": "c"}})
+			]
+		);
+	}
+
+	#[test]
+	fn explode_missing_path() {
+		assert_eq!(
+			entries(json!({"message": "a", "events": "b"}), Some("events")),
+			vec![json!({"message": "a", "events": "b"})]
+		);
+	}
+}
@@ -1,6 +1,5 @@
-use crate::log_settings::LogSettings;
 use lazy_static::lazy_static;
-use mlua::{Error as LuaError, Lua};
+use mlua::{Error as LuaError, Function, Lua, Table, Value as LuaValue};
 use regex::Regex;
 use serde_json::{Map, Value};
 use std::fmt::Write as _;
@@ -10,20 +9,111 @@ lazy_static! {
 	static ref LUA_STRING_ESCAPE: Regex = Regex::new(r"([\n])").unwrap();
 }
 
-pub fn show_log_entry(log_entry: &Map<String, Value>, filter_expr: &str, implicit_return: bool, log_settings: &LogSettings) -> Result<bool, LuaError> {
-	let lua = Lua::new();
+/// A filter expression compiled once into a lua function.
+///
+/// The prefix of a log entry is available as `fblog_prefix`.
+///
+/// Every log entry is written into a fresh environment of the function, so globals
+/// assigned while filtering one entry are not visible to the next one. `_G` is the environment itself,
+/// other globals are looked up in the globals of the lua state.
+pub struct Filter {
+	lua: Lua,
+	function: Function,
+	environment: Table,
+	expression: FilterExpression,
+}
+
+/// The source of a filter. Lua states can't be shared between threads, so every thread compiles its own filter.
+#[derive(Clone)]
+pub struct FilterExpression {
+	filter_expr: String,
+	implicit_return: bool,
+}
+
+impl FilterExpression {
+	pub fn compile(&self) -> Result<Filter, LuaError> {
+		Filter::new(&self.filter_expr, self.implicit_return)
+	}
+}
+
+impl Filter {
+	pub fn new(filter_expr: &str, implicit_return: bool) -> Result<Filter, LuaError> {
+		let lua = Lua::new();
+
+		let environment = lua.create_table()?;
+		let environment_meta = lua.create_table()?;
+		environment_meta.raw_set("__index", lua.globals())?;
+		environment.set_metatable(Some(environment_meta))?;
+
+		let source = if implicit_return {
+			format!("return {filter_expr};")
+		} else {
+			filter_expr.to_string()
+		};
+		let function = lua.load(source).set_name("filter").set_environment(environment.clone()).into_function()?;
+
+		Ok(Filter {
+			lua,
+			function,
+			environment,
+			expression: FilterExpression {
+				filter_expr: filter_expr.to_string(),
+				implicit_return,
+			},
+		})
+	}
+
+	pub fn expression(&self) -> &FilterExpression {
+		&self.expression
+	}
+
+	pub fn show_log_entry(&self, log_entry: &Map<String, Value>, maybe_prefix: Option<&str>) -> Result<bool, LuaError> {
+		self.environment.clear()?;
+		self.environment.raw_set("_G", &self.environment)?;
+		self.write_record(&self.environment, log_entry)?;
+		if let Some(prefix) = maybe_prefix {
+			self.environment.raw_set("fblog_prefix", prefix.trim())?;
+		}
 
-	let script = object_to_record(log_entry, false);
-	if log_settings.print_lua {
-		println!("{script}");
+		self.function.call::<bool>(())
 	}
-	lua.load(&script).exec()?;
 
-	if implicit_return {
-		lua.load(format!("return {filter_expr};")).eval::<bool>()
-	} else {
-		lua.load(filter_expr).eval::<bool>()
+	fn write_record(&self, table: &Table, object: &Map<String, Value>) -> Result<(), LuaError> {
+		for (key, value) in object {
+			let key_name = LUA_IDENTIFIER_CLEANUP.replace_all(key, "_");
+			table.raw_set(key_name.as_ref(), self.to_lua_value(value, true)?)?;
+		}
+		Ok(())
 	}
+
+	fn to_lua_value(&self, value: &Value, allow_nested: bool) -> Result<LuaValue, LuaError> {
+		Ok(match value {
+			Value::String(string_value) => LuaValue::String(self.lua.create_string(string_value)?),
+			Value::Bool(bool_value) => LuaValue::Boolean(*bool_value),
+			Value::Number(number_value) => match number_value.as_i64() {
+				Some(integer) => LuaValue::Integer(integer),
+				None => LuaValue::Number(number_value.as_f64().unwrap_or(f64::NAN)),
+			},
+			Value::Object(nested_object) if allow_nested => {
+				let table = self.lua.create_table_with_capacity(0, nested_object.len())?;
+				self.write_record(&table, nested_object)?;
+				LuaValue::Table(table)
+			}
+			Value::Array(array_values) if allow_nested => {
+				let table = self.lua.create_table_with_capacity(array_values.len(), 0)?;
+				for array_value in array_values {
+					table.raw_push(self.to_lua_value(array_value, false)?)?;
+				}
+				LuaValue::Table(table)
+			}
+			_ => LuaValue::String(self.lua.create_string("unsupported")?),
+		})
+	}
+}
+
+/// The log entry as lua table, shown with `--print-lua`.
+pub fn lua_record(log_entry: &Map<String, Value>) -> String {
+	object_to_record(log_entry, false)
 }
 
 fn object_to_record(object: &Map<String, Value>, nested: bool) -> String {
@@ -85,6 +175,10 @@ fn escape_lua_string(src: &str) -> String {
 mod tests {
 	use super::*;
 
+	fn show_log_entry(log_entry: &Map<String, Value>, filter_expr: &str, implicit_return: bool) -> Result<bool, LuaError> {
+		Filter::new(filter_expr, implicit_return)?.show_log_entry(log_entry, None)
+	}
+
 	fn test_log_entry() -> Map<String, Value> {
 		let mut map = Map::new();
 		map.insert("message".to_string(), Value::String("something happened".to_string()));
@@ -116,97 +210,127 @@ mod tests {
 	#[test]
 	fn allow_all() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(show_log_entry(&log_entry, "true", true, &LogSettings::new_default_settings()).unwrap());
+		assert!(show_log_entry(&log_entry, "true", true).unwrap());
 	}
 
 	#[test]
 	fn deny_all() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(!show_log_entry(&log_entry, "false", true, &LogSettings::new_default_settings()).unwrap());
+		assert!(!show_log_entry(&log_entry, "false", true).unwrap());
 	}
 
 	#[test]
 	fn filter_process() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(show_log_entry(&log_entry, r#"process == "rust""#, true, &LogSettings::new_default_settings()).unwrap());
-		assert!(!show_log_entry(&log_entry, r#"process == "meep""#, true, &LogSettings::new_default_settings()).unwrap());
+		assert!(show_log_entry(&log_entry, r#"process == "rust""#, true).unwrap());
+		assert!(!show_log_entry(&log_entry, r#"process == "meep""#, true).unwrap());
 	}
 
 	#[test]
 	fn filter_logical_operators() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(show_log_entry(&log_entry, r#"process == "rust" and fu == "bower""#, true, &LogSettings::new_default_settings()).unwrap());
-		assert!(show_log_entry(&log_entry, r#"process == "rust" or fu == "bauer""#, true, &LogSettings::new_default_settings()).unwrap());
+		assert!(show_log_entry(&log_entry, r#"process == "rust" and fu == "bower""#, true).unwrap());
+		assert!(show_log_entry(&log_entry, r#"process == "rust" or fu == "bauer""#, true).unwrap());
 	}
 
 	#[test]
 	fn filter_contains() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(
-			show_log_entry(
-				&log_entry,
-				r#"string.find(message, "something") ~= nil"#,
-				true,
-				&LogSettings::new_default_settings()
-			)
-			.unwrap()
-		);
-		assert!(!show_log_entry(&log_entry, r#"string.find(message, "bla") ~= nil"#, true, &LogSettings::new_default_settings()).unwrap());
+		assert!(show_log_entry(&log_entry, r#"string.find(message, "something") ~= nil"#, true).unwrap());
+		assert!(!show_log_entry(&log_entry, r#"string.find(message, "bla") ~= nil"#, true).unwrap());
 	}
 
 	#[test]
 	fn filter_regex() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(show_log_entry(&log_entry, r#"string.find(fu, "bow.*") ~= nil"#, true, &LogSettings::new_default_settings()).unwrap());
-		assert!(!show_log_entry(&log_entry, r#"string.find(fu, "bow.*sd") ~= nil"#, true, &LogSettings::new_default_settings()).unwrap());
+		assert!(show_log_entry(&log_entry, r#"string.find(fu, "bow.*") ~= nil"#, true).unwrap());
+		assert!(!show_log_entry(&log_entry, r#"string.find(fu, "bow.*sd") ~= nil"#, true).unwrap());
 	}
 
 	#[test]
 	fn unknown_variable() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(
-			!show_log_entry(
-				&log_entry,
-				r#"sdkfjsdfjsf ~= nil and string.find(sdkfjsdfjsf, "bow.*") ~= nil"#,
-				true,
-				&LogSettings::new_default_settings()
-			)
-			.unwrap()
-		);
+		assert!(!show_log_entry(&log_entry, r#"sdkfjsdfjsf ~= nil and string.find(sdkfjsdfjsf, "bow.*") ~= nil"#, true).unwrap());
 	}
 
 	#[test]
 	fn no_implicit_return() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(
-			show_log_entry(
-				&log_entry,
-				r#"if 3 > 2 then return true else return false end"#,
-				false,
-				&LogSettings::new_default_settings()
-			)
-			.unwrap()
-		);
-		assert!(
-			!show_log_entry(
-				&log_entry,
-				r#"if 1 > 2 then return true else return false end"#,
-				false,
-				&LogSettings::new_default_settings()
-			)
-			.unwrap()
-		);
+		assert!(show_log_entry(&log_entry, r#"if 3 > 2 then return true else return false end"#, false).unwrap());
+		assert!(!show_log_entry(&log_entry, r#"if 1 > 2 then return true else return false end"#, false).unwrap());
 	}
 
 	#[test]
 	fn neted() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(show_log_entry(&log_entry, r#"nested.log_level == "debug""#, true, &LogSettings::new_default_settings()).unwrap());
+		assert!(show_log_entry(&log_entry, r#"nested.log_level == "debug""#, true).unwrap());
 	}
 
 	#[test]
 	fn nested_with_array() {
 		let log_entry: Map<String, Value> = test_log_entry();
-		assert!(show_log_entry(&log_entry, r#"nested_with_array.array[2] == "b""#, true, &LogSettings::new_default_settings()).unwrap());
+		assert!(show_log_entry(&log_entry, r#"nested_with_array.array[2] == "b""#, true).unwrap());
+	}
+
+	#[test]
+	fn reuse_filter_for_multiple_entries() {
+		let filter = Filter::new(r#"process == "rust""#, true).unwrap();
+		let mut log_entry: Map<String, Value> = test_log_entry();
+		assert!(filter.show_log_entry(&log_entry, None).unwrap());
+
+		log_entry.insert("process".to_string(), Value::String("meep".to_string()));
+		assert!(!filter.show_log_entry(&log_entry, None).unwrap());
+	}
+
+	#[test]
+	fn no_leaking_values_between_entries() {
+		let filter = Filter::new("fu == nil", true).unwrap();
+		let mut log_entry: Map<String, Value> = test_log_entry();
+		assert!(!filter.show_log_entry(&log_entry, None).unwrap());
+
+		log_entry.remove("fu");
+		assert!(filter.show_log_entry(&log_entry, None).unwrap());
+	}
+
+	#[test]
+	fn no_leaking_globals_between_entries() {
+		let filter = Filter::new("if seen then return false end seen = true return true", false).unwrap();
+		let log_entry: Map<String, Value> = test_log_entry();
+		assert!(filter.show_log_entry(&log_entry, None).unwrap());
+		assert!(filter.show_log_entry(&log_entry, None).unwrap());
+	}
+
+	#[test]
+	fn no_leaking_state_through_g() {
+		let log_entry: Map<String, Value> = test_log_entry();
+		let filter = Filter::new("if _G.seen then return false end _G.seen = true return true", false).unwrap();
+		assert!(filter.show_log_entry(&log_entry, None).unwrap());
+		assert!(filter.show_log_entry(&log_entry, None).unwrap());
+
+		let filter = Filter::new("if seen then return false end rawset(_G, \"seen\", true) return true", false).unwrap();
+		assert!(filter.show_log_entry(&log_entry, None).unwrap());
+		assert!(filter.show_log_entry(&log_entry, None).unwrap());
+	}
+
+	#[test]
+	fn standard_library() {
+		let log_entry: Map<String, Value> = test_log_entry();
+		assert!(show_log_entry(&log_entry, r#"os.getenv("PATH") ~= nil"#, true).unwrap());
+		assert!(show_log_entry(&log_entry, "setmetatable({}, {}) ~= nil", true).unwrap());
+		assert!(show_log_entry(&log_entry, r#"require("string").upper(process) == "RUST""#, true).unwrap());
+	}
+
+	#[test]
+	fn invalid_filter_expression() {
+		assert!(Filter::new("process ==", true).is_err());
+	}
+
+	#[test]
+	fn filter_prefix() {
+		let filter = Filter::new(r#"fblog_prefix == "pod/api-1""#, true).unwrap();
+		let log_entry: Map<String, Value> = test_log_entry();
+		assert!(filter.show_log_entry(&log_entry, Some("pod/api-1 ")).unwrap());
+		assert!(!filter.show_log_entry(&log_entry, Some("pod/api-2 ")).unwrap());
+		assert!(!filter.show_log_entry(&log_entry, None).unwrap());
 	}
 }
@@ -0,0 +1,197 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Reads a file and keeps waiting for appended data instead of reporting the end of the file.
+///
+/// When the end of the file is reached the path is checked again. A different file behind the
+/// path (logrotate moved the old one away) is opened from the start after the old one is drained,
+/// including lines appended between reaching its end and the rotation.
+/// A file that became smaller than what was already read (copytruncate) is read again from the start.
+pub struct FollowReader {
+	path: PathBuf,
+	file: File,
+	identity: Option<FileIdentity>,
+	position: u64,
+	/// The file that replaced the followed one, read once the followed file is drained.
+	rotated: Option<(File, Option<FileIdentity>)>,
+}
+
+impl FollowReader {
+	pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<FollowReader> {
+		let path = path.into();
+		let file = File::open(&path)?;
+		let identity = file_identity(&file.metadata()?);
+		Ok(FollowReader {
+			path,
+			file,
+			identity,
+			position: 0,
+			rotated: None,
+		})
+	}
+
+	fn reopen_if_rotated(&mut self) -> io::Result<bool> {
+		let metadata = match fs::metadata(&self.path) {
+			Ok(metadata) => metadata,
+			// The file might be missing for a short time while it gets rotated.
+			Err(_) => return Ok(false),
+		};
+
+		if file_identity(&metadata) != self.identity {
+			let file = match File::open(&self.path) {
+				Ok(file) => file,
+				Err(_) => return Ok(false),
+			};
+			let identity = file_identity(&file.metadata()?);
+			self.rotated = Some((file, identity));
+			Ok(true)
+		} else if metadata.len() < self.position {
+			self.file.seek(SeekFrom::Start(0))?;
+			self.position = 0;
+			Ok(true)
+		} else {
+			Ok(false)
+		}
+	}
+}
+
+impl Read for FollowReader {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		loop {
+			let read = self.file.read(buf)?;
+			if read > 0 {
+				self.position += read as u64;
+				return Ok(read);
+			}
+
+			if let Some((file, identity)) = self.rotated.take() {
+				self.file = file;
+				self.identity = identity;
+				self.position = 0;
+				continue;
+			}
+			if !self.reopen_if_rotated()? {
+				thread::sleep(POLL_INTERVAL);
+			}
+		}
+	}
+}
+
+#[cfg(unix)]
+type FileIdentity = (u64, u64);
+
+#[cfg(unix)]
+fn file_identity(metadata: &Metadata) -> Option<FileIdentity> {
+	use std::os::unix::fs::MetadataExt;
+	Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+type FileIdentity = ();
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &Metadata) -> Option<FileIdentity> {
+	// Without inodes only truncation can be detected.
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::fs::OpenOptions;
+	use std::io::{BufRead, BufReader, Write};
+
+	fn test_file(name: &str) -> PathBuf {
+		let mut path = env::temp_dir();
+		path.push(format!("fblog-follow-{}-{}.log", std::process::id(), name));
+		path
+	}
+
+	fn append(path: &PathBuf, text: &str) {
+		let mut file = OpenOptions::new().create(true).append(true).open(path).unwrap();
+		file.write_all(text.as_bytes()).unwrap();
+	}
+
+	fn next_line(reader: &mut BufReader<FollowReader>) -> String {
+		let mut line = String::new();
+		reader.read_line(&mut line).unwrap();
+		line
+	}
+
+	#[test]
+	fn follow_appended_lines() {
+		let path = test_file("append");
+		fs::write(&path, "a\n").unwrap();
+		let mut reader = BufReader::new(FollowReader::open(&path).unwrap());
+		assert_eq!(next_line(&mut reader), "a\n");
+
+		append(&path, "b\n");
+		assert_eq!(next_line(&mut reader), "b\n");
+
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn follow_truncated_file() {
+		let path = test_file("truncate");
+		fs::write(&path, "first\nsecond\n").unwrap();
+		let mut reader = BufReader::new(FollowReader::open(&path).unwrap());
+		assert_eq!(next_line(&mut reader), "first\n");
+		assert_eq!(next_line(&mut reader), "second\n");
+
+		fs::write(&path, "third\n").unwrap();
+		assert_eq!(next_line(&mut reader), "third\n");
+
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn follow_rotated_file() {
+		let path = test_file("rotate");
+		let rotated_path = test_file("rotate.1");
+		fs::write(&path, "first\n").unwrap();
+		let mut reader = BufReader::new(FollowReader::open(&path).unwrap());
+		assert_eq!(next_line(&mut reader), "first\n");
+
+		append(&path, "second\n");
+		fs::rename(&path, &rotated_path).unwrap();
+		fs::write(&path, "third\n").unwrap();
+		assert_eq!(next_line(&mut reader), "second\n");
+		assert_eq!(next_line(&mut reader), "third\n");
+
+		fs::remove_file(&path).unwrap();
+		fs::remove_file(&rotated_path).unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn follow_lines_appended_before_rotation() {
+		let path = test_file("rotate-appended");
+		let rotated_path = test_file("rotate-appended.1");
+		fs::write(&path, "first\n").unwrap();
+		let mut reader = FollowReader::open(&path).unwrap();
+		let mut first = [0; 6];
+		reader.read_exact(&mut first).unwrap();
+		assert_eq!(reader.file.read(&mut [0; 16]).unwrap(), 0);
+
+		// Within one poll interval: appended, rotated and replaced
+		append(&path, "second\n");
+		fs::rename(&path, &rotated_path).unwrap();
+		fs::write(&path, "third\n").unwrap();
+		assert!(reader.reopen_if_rotated().unwrap());
+
+		let mut reader = BufReader::new(reader);
+		assert_eq!(next_line(&mut reader), "second\n");
+		assert_eq!(next_line(&mut reader), "third\n");
+
+		fs::remove_file(&path).unwrap();
+		fs::remove_file(&rotated_path).unwrap();
+	}
+}
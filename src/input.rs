@@ -0,0 +1,229 @@
+use crate::cri::CriLines;
+use crate::encoding;
+use crate::log::{self, FlatEntry};
+use crate::log_settings::LogSettings;
+use crate::process::{self, ParsedLine};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::path::Path;
+use yansi::{Color, Paint};
+
+static SOURCE_COLORS: &[Color] = &[Color::Cyan, Color::Magenta, Color::Yellow, Color::Blue, Color::Green, Color::Red];
+
+/// The input a log entry was read from.
+pub struct Source {
+	pub name: String,
+	pub tag: Option<String>,
+}
+
+impl Source {
+	pub fn new(name: &str) -> Source {
+		Source {
+			name: name.to_string(),
+			tag: None,
+		}
+	}
+
+	/// Creates a source with a colored tag, so entries of merged inputs can be told apart.
+	pub fn with_tag(name: &str, index: usize) -> Source {
+		let short_name = Path::new(name).file_name().and_then(|n| n.to_str()).unwrap_or(name);
+		let color = SOURCE_COLORS[index % SOURCE_COLORS.len()];
+		Source {
+			name: name.to_string(),
+			tag: Some(format!("{}", short_name.fg(color).bold())),
+		}
+	}
+}
+
+/// A line read from an input. Lines of merged inputs are parsed to find their timestamps,
+/// the parsed log entries are kept so the line is not parsed again when it is processed.
+pub struct InputLine {
+	pub text: String,
+	/// The result of parsing the line, `None` if it was not parsed yet.
+	pub parsed: Option<Option<ParsedLine<'static>>>,
+}
+
+impl InputLine {
+	pub fn new(text: String) -> InputLine {
+		InputLine { text, parsed: None }
+	}
+}
+
+/// Consecutive lines of one input. Only the first line has a timestamp (if any),
+/// the others could not be placed in time and stay with it.
+struct Group {
+	timestamp: Option<DateTime<Utc>>,
+	lines: Vec<io::Result<InputLine>>,
+}
+
+struct InputLines {
+	lines: CriLines<encoding::Lines<Box<dyn BufRead + Send>>>,
+	pending: Option<(Option<DateTime<Utc>>, io::Result<InputLine>)>,
+}
+
+impl InputLines {
+	fn next_line(&mut self, log_settings: &LogSettings) -> Option<(Option<DateTime<Utc>>, io::Result<InputLine>)> {
+		self.lines.next().map(|line| {
+			let input_line = line.map(|text| {
+				let parsed = process::parse_log_entries(log_settings, &text).map(ParsedLine::into_owned);
+				InputLine { text, parsed: Some(parsed) }
+			});
+			let timestamp = input_line.as_ref().ok().and_then(|input_line| line_timestamp(log_settings, input_line));
+			(timestamp, input_line)
+		})
+	}
+
+	fn next_group(&mut self, log_settings: &LogSettings) -> Option<Group> {
+		let (timestamp, first_line) = self.pending.take().or_else(|| self.next_line(log_settings))?;
+		let mut lines = vec![first_line];
+		while let Some((next_timestamp, next_line)) = self.next_line(log_settings) {
+			if next_timestamp.is_some() {
+				self.pending = Some((next_timestamp, next_line));
+				break;
+			}
+			lines.push(next_line);
+		}
+		Some(Group { timestamp, lines })
+	}
+}
+
+fn line_timestamp(log_settings: &LogSettings, input_line: &InputLine) -> Option<DateTime<Utc>> {
+	let parsed_line = input_line.parsed.as_ref()?.as_ref()?;
+	log::get_parsed_timestamp(&FlatEntry::new(parsed_line.log_entries.first()?.as_ref().ok()?), log_settings)
+}
+
+/// Merges the lines of multiple inputs in the order of the timestamps of their log entries.
+/// Yields the index of the input together with every line.
+pub struct MergedLines<'a> {
+	log_settings: &'a LogSettings,
+	inputs: Vec<InputLines>,
+	heads: Vec<Option<Group>>,
+	current_index: usize,
+	current_lines: VecDeque<io::Result<InputLine>>,
+}
+
+impl<'a> MergedLines<'a> {
+	pub fn new(log_settings: &'a LogSettings, readers: Vec<Box<dyn BufRead + Send>>) -> MergedLines<'a> {
+		let mut inputs: Vec<InputLines> = readers
+			.into_iter()
+			.map(|reader| InputLines {
+				lines: CriLines::new(encoding::Lines::new(reader, log_settings.encoding)),
+				pending: None,
+			})
+			.collect();
+		let heads = inputs.iter_mut().map(|input| input.next_group(log_settings)).collect();
+		MergedLines {
+			log_settings,
+			inputs,
+			heads,
+			current_index: 0,
+			current_lines: VecDeque::new(),
+		}
+	}
+}
+
+impl Iterator for MergedLines<'_> {
+	type Item = (usize, io::Result<InputLine>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some(line) = self.current_lines.pop_front() {
+				return Some((self.current_index, line));
+			}
+
+			// Groups without a timestamp sort first, ties are resolved by the order of the inputs.
+			let (_, index) = self
+				.heads
+				.iter()
+				.enumerate()
+				.filter_map(|(index, head)| head.as_ref().map(|group| (group.timestamp, index)))
+				.min()?;
+			let group = self.heads[index].take()?;
+			self.heads[index] = self.inputs[index].next_group(self.log_settings);
+			self.current_index = index;
+			self.current_lines = group.lines.into();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn reader(lines: &'static str) -> Box<dyn BufRead + Send> {
+		Box::new(io::BufReader::new(lines.as_bytes()))
+	}
+
+	fn merge(readers: Vec<Box<dyn BufRead + Send>>) -> Vec<(usize, String)> {
+		let log_settings = LogSettings::new_default_settings();
+		MergedLines::new(&log_settings, readers)
+			.map(|(index, line)| (index, line.unwrap().text))
+			.collect()
+	}
+
+	#[test]
+	fn merge_by_timestamp() {
+		let api = reader(
+			r#"{"time": "2024-05-21T10:00:00Z", "message": "api 1"}
+{"time": "2024-05-21T10:00:02Z", "message": "api 2"}
+"#,
+		);
+		let worker = reader(
+			r#"{"time": "2024-05-21T10:00:01Z", "message": "worker 1"}
+{"time": "2024-05-21T10:00:03Z", "message": "worker 2"}
+"#,
+		);
+
+		let merged: Vec<usize> = merge(vec![api, worker]).into_iter().map(|(index, _)| index).collect();
+		assert_eq!(merged, vec![0, 1, 0, 1]);
+	}
+
+	#[test]
+	fn keep_entries_without_timestamp_with_previous_entry() {
+		let api = reader(
+			r#"{"time": "2024-05-21T10:00:00Z", "message": "api 1"}
+no timestamp
+{"message": "no timestamp either"}
+{"time": "2024-05-21T10:00:02Z", "message": "api 2"}
+"#,
+		);
+		let worker = reader(
+			r#"{"time": "2024-05-21T10:00:01Z", "message": "worker 1"}
+"#,
+		);
+
+		let merged = merge(vec![api, worker]);
+		assert_eq!(
+			merged.iter().map(|(index, line)| (*index, &line[..12])).collect::<Vec<_>>(),
+			vec![
+				(0, r#"{"time": "20"#),
+				(0, "no timestamp"),
+				(0, r#"{"message": "#),
+				(1, r#"{"time": "20"#),
+				(0, r#"{"time": "20"#),
+			]
+		);
+	}
+
+	#[test]
+	fn keep_parsed_log_entries() {
+		let log_settings = LogSettings::new_default_settings();
+		let api = reader(
+			r#"{"time": "2024-05-21T10:00:00Z", "message": "api 1"}
+no timestamp
+"#,
+		);
+
+		let parsed: Vec<Option<usize>> = MergedLines::new(&log_settings, vec![api])
+			.map(|(_, line)| {
+				line
+					.unwrap()
+					.parsed
+					.expect("Merged lines are parsed")
+					.map(|parsed_line| parsed_line.log_entries.len())
+			})
+			.collect();
+		assert_eq!(parsed, vec![Some(1), None]);
+	}
+}
@@ -0,0 +1,260 @@
+use crate::encoding::{self, Encoding};
+use serde_json::{Deserializer, Value};
+use std::io::{self, BufRead};
+
+const RECORD_SEPARATOR: u8 = 0x1e;
+
+pub enum JsonStreamItem {
+	Value(Value),
+	/// A line that is not valid json. Reading continues with the next line.
+	Invalid(String),
+}
+
+/// Reads json values independent of line breaks.
+///
+/// Supports pretty printed values spanning multiple lines, multiple values on one line (`{..}{..}`)
+/// and json text sequences (RFC 7464), where every value is preceded by a record separator.
+/// The elements of a top-level array of objects are read one at a time, so the array is never held in memory.
+pub struct JsonStream<'a> {
+	input: &'a mut dyn BufRead,
+	encoding: Encoding,
+	buffer: Vec<u8>,
+	line: Vec<u8>,
+	position: usize,
+	eof: bool,
+	/// Progress of scanning an object or array spanning multiple lines for its end, so every read byte is only scanned once.
+	pending: Option<Scan>,
+	/// Inside a top-level array of objects, whose elements are read as values.
+	in_array: bool,
+}
+
+impl<'a> JsonStream<'a> {
+	pub fn new(input: &'a mut dyn BufRead, encoding: Encoding) -> JsonStream<'a> {
+		JsonStream {
+			input,
+			encoding,
+			buffer: Vec::new(),
+			line: Vec::new(),
+			position: 0,
+			eof: false,
+			pending: None,
+			in_array: false,
+		}
+	}
+
+	fn skip_separators(&mut self) {
+		while let Some(byte) = self.buffer.get(self.position) {
+			match byte {
+				b',' if self.in_array => (),
+				b']' if self.in_array => self.in_array = false,
+				byte if byte.is_ascii_whitespace() || *byte == RECORD_SEPARATOR => (),
+				_ => return,
+			}
+			self.position += 1;
+		}
+	}
+
+	/// Whether an array of objects starts at the position, `None` if more input is needed to know.
+	fn starts_array_of_objects(&self) -> Option<bool> {
+		if self.in_array || self.pending.is_some() || self.buffer[self.position] != b'[' {
+			return Some(false);
+		}
+		match self.buffer[self.position + 1..].iter().find(|byte| !byte.is_ascii_whitespace()) {
+			Some(byte) => Some(*byte == b'{'),
+			None if self.eof => Some(false),
+			None => None,
+		}
+	}
+
+	fn read_more(&mut self) -> io::Result<()> {
+		self.buffer.drain(..self.position);
+		self.position = 0;
+		self.line.clear();
+		if self.input.read_until(b'\n', &mut self.line)? == 0 {
+			self.eof = true;
+		}
+		self.buffer.extend(encoding::decode_line(&self.line, self.encoding).as_bytes());
+		Ok(())
+	}
+
+	/// Parses the value at the current position once it is complete. Returns `None` if more input is needed.
+	fn parse_value(&mut self) -> Option<JsonStreamItem> {
+		let rest = &self.buffer[self.position..];
+		let complete = match &mut self.pending {
+			// Syntax errors on the first line of a value are found right away
+			None => rest,
+			Some(scan) => match scan.end(rest) {
+				Some(end) => &rest[..end],
+				None if self.eof => rest,
+				None => return None,
+			},
+		};
+		let mut values = Deserializer::from_slice(complete).into_iter::<Value>();
+		match values.next() {
+			Some(Ok(value)) => {
+				self.position += values.byte_offset();
+				self.pending = None;
+				Some(JsonStreamItem::Value(value))
+			}
+			Some(Err(e)) if e.is_eof() && !self.eof && self.pending.is_none() && matches!(rest[0], b'{' | b'[') => {
+				self.pending = Some(Scan::default());
+				None
+			}
+			_ => self.take_invalid_line(),
+		}
+	}
+
+	fn take_invalid_line(&mut self) -> Option<JsonStreamItem> {
+		let rest = &self.buffer[self.position..];
+		let end = match rest.iter().position(|b| *b == b'\n') {
+			Some(end) => end,
+			None if self.eof => rest.len(),
+			None => return None,
+		};
+		let line = String::from_utf8_lossy(&rest[..end]).trim_end_matches('\r').to_string();
+		self.position += (end + 1).min(rest.len());
+		self.pending = None;
+		Some(JsonStreamItem::Invalid(line))
+	}
+}
+
+impl Iterator for JsonStream<'_> {
+	type Item = io::Result<JsonStreamItem>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			self.skip_separators();
+			if self.position < self.buffer.len() {
+				match self.starts_array_of_objects() {
+					Some(true) => {
+						self.position += 1;
+						self.in_array = true;
+						continue;
+					}
+					Some(false) => {
+						if let Some(item) = self.parse_value() {
+							return Some(Ok(item));
+						}
+					}
+					None => (),
+				}
+			} else if self.eof {
+				return None;
+			}
+
+			if let Err(e) = self.read_more() {
+				self.eof = true;
+				return Some(Err(e));
+			}
+		}
+	}
+}
+
+/// Finds the end of an object or array by counting brackets outside of strings.
+#[derive(Default)]
+struct Scan {
+	/// Scanned bytes from the start of the value.
+	offset: usize,
+	depth: usize,
+	in_string: bool,
+	escaped: bool,
+}
+
+impl Scan {
+	/// Continues scanning the value and returns its length once the closing bracket is found.
+	fn end(&mut self, value: &[u8]) -> Option<usize> {
+		while let Some(byte) = value.get(self.offset) {
+			self.offset += 1;
+			if self.in_string {
+				match byte {
+					_ if self.escaped => self.escaped = false,
+					b'\\' => self.escaped = true,
+					b'"' => self.in_string = false,
+					_ => (),
+				}
+			} else {
+				match byte {
+					b'"' => self.in_string = true,
+					b'{' | b'[' => self.depth += 1,
+					b'}' | b']' => {
+						self.depth = self.depth.saturating_sub(1);
+						if self.depth == 0 {
+							return Some(self.offset);
+						}
+					}
+					_ => (),
+				}
+			}
+		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn read_all(input: &str) -> Vec<Value> {
+		let mut reader = io::BufReader::new(input.as_bytes());
+		JsonStream::new(&mut reader, Encoding::Utf8)
+			.map(|item| match item.unwrap() {
+				JsonStreamItem::Value(value) => value,
+				JsonStreamItem::Invalid(line) => Value::String(format!("invalid: {line}")),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn pretty_printed() {
+		let input = "{\n  \"message\": \"first\",\n  \"nested\": {\n    \"a\": 1\n  }\n}\n{\n  \"message\": \"second\"\n}\n";
+		assert_eq!(
+			read_all(input),
+			vec![json!({"message": "first", "nested": {"a": 1}}), json!({"message": "second"})]
+		);
+	}
+
+	#[test]
+	fn concatenated() {
+		let input = "{\"message\": \"first\"}{\"message\": \"second\"} {\"message\": \"third\"}\n";
+		assert_eq!(
+			read_all(input),
+			vec![json!({"message": "first"}), json!({"message": "second"}), json!({"message": "third"})]
+		);
+	}
+
+	#[test]
+	fn json_text_sequence() {
+		let input = "\x1e{\"message\": \"first\"}\n\x1e{\"message\":\n\"second\"}\n";
+		assert_eq!(read_all(input), vec![json!({"message": "first"}), json!({"message": "second"})]);
+	}
+
+	#[test]
+	fn brackets_in_strings() {
+		let input = "{\n  \"message\": \"closing } and ] \\\" {\",\n  \"list\": [\n    1,\n    2\n  ]\n}\n[\n  1\n]\n";
+		assert_eq!(read_all(input), vec![json!({"message": "closing } and ] \" {", "list": [1, 2]}), json!([1])]);
+	}
+
+	#[test]
+	fn array_elements() {
+		let input = "[\n  {\n    \"message\": \"first\"\n  },\n  {\"message\": [1]}\n]\n[1, 2]\n[]\n";
+		assert_eq!(
+			read_all(input),
+			vec![json!({"message": "first"}), json!({"message": [1]}), json!([1, 2]), json!([])]
+		);
+	}
+
+	#[test]
+	fn invalid_lines() {
+		let input = "{\"message\": \"first\"}\nnot json\n{\"message\": \"second\"}\n{\"message\": ";
+		assert_eq!(
+			read_all(input),
+			vec![
+				json!({"message": "first"}),
+				json!("invalid: not json"),
+				json!({"message": "second"}),
+				json!("invalid: {\"message\": "),
+			]
+		);
+	}
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+	UnknownLevel(String, Vec<String>),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::UnknownLevel(level, order) => write!(f, "Unknown level `{level}`, known levels are {}", order.join(", ")),
+		}
+	}
+}
+
+/// The severity ladder from the least to the most severe level, e.g. `trace` to `fatal`.
+/// Aliases like `warning`, `err` or the numeric levels of bunyan and syslog map to a level of the ladder.
+pub struct Severities {
+	order: Vec<String>,
+	aliases: BTreeMap<String, String>,
+}
+
+impl Severities {
+	pub fn new(order: Vec<String>, aliases: BTreeMap<String, String>) -> Severities {
+		Severities { order, aliases }
+	}
+
+	/// Position of the level on the ladder, `None` for levels that are neither on the ladder nor an alias.
+	pub fn rank(&self, level: &str) -> Option<usize> {
+		let level = level.trim().to_lowercase();
+		let level = self.aliases.get(&level).unwrap_or(&level);
+		self.order.iter().position(|ordered| ordered.eq_ignore_ascii_case(level))
+	}
+}
+
+/// Shows log entries by their level. Entries without a known level are hidden.
+pub struct LevelFilter {
+	severities: Severities,
+	shown: Vec<bool>,
+}
+
+impl LevelFilter {
+	/// Shows the level and every more severe level.
+	pub fn min_level(severities: Severities, min_level: &str) -> Result<LevelFilter, Error> {
+		let min_rank = rank_of_name(&severities, min_level)?;
+		let shown = (0..severities.order.len()).map(|rank| rank >= min_rank).collect();
+		Ok(LevelFilter { severities, shown })
+	}
+
+	/// Shows only the given levels.
+	pub fn levels(severities: Severities, levels: &[&str]) -> Result<LevelFilter, Error> {
+		let mut shown = vec![false; severities.order.len()];
+		for level in levels {
+			shown[rank_of_name(&severities, level)?] = true;
+		}
+		Ok(LevelFilter { severities, shown })
+	}
+
+	pub fn show(&self, level: Option<&str>) -> bool {
+		level.and_then(|level| self.severities.rank(level)).is_some_and(|rank| self.shown[rank])
+	}
+}
+
+fn rank_of_name(severities: &Severities, level: &str) -> Result<usize, Error> {
+	severities
+		.rank(level)
+		.ok_or_else(|| Error::UnknownLevel(level.to_string(), severities.order.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::Config;
+
+	fn default_severities() -> Severities {
+		let config = Config::new();
+		Severities::new(config.level_order, config.level_aliases)
+	}
+
+	#[test]
+	fn rank_levels() {
+		let severities = default_severities();
+		assert_eq!(severities.rank("trace"), Some(0));
+		assert_eq!(severities.rank("WARN"), Some(3));
+		assert_eq!(severities.rank(" warning "), Some(3));
+		assert_eq!(severities.rank("err"), Some(4));
+		assert_eq!(severities.rank("crit"), Some(5));
+		assert_eq!(severities.rank("panic"), Some(5));
+		assert_eq!(severities.rank("30"), Some(2));
+		assert_eq!(severities.rank("60"), Some(5));
+		assert_eq!(severities.rank("0"), Some(5));
+		assert_eq!(severities.rank("4"), Some(3));
+		assert_eq!(severities.rank("7"), Some(1));
+		assert_eq!(severities.rank("unknown"), None);
+	}
+
+	#[test]
+	fn min_level() {
+		let level_filter = LevelFilter::min_level(default_severities(), "warning").unwrap();
+		assert!(!level_filter.show(Some("info")));
+		assert!(!level_filter.show(Some("30")));
+		assert!(level_filter.show(Some("WARN")));
+		assert!(level_filter.show(Some("50")));
+		assert!(level_filter.show(Some("crit")));
+		assert!(!level_filter.show(Some("unknown")));
+		assert!(!level_filter.show(None));
+	}
+
+	#[test]
+	fn selected_levels() {
+		let level_filter = LevelFilter::levels(default_severities(), &["error", "fatal"]).unwrap();
+		assert!(!level_filter.show(Some("warn")));
+		assert!(level_filter.show(Some("err")));
+		assert!(level_filter.show(Some("60")));
+		assert!(level_filter.show(Some("2")));
+	}
+
+	#[test]
+	fn unknown_level_name() {
+		let error = LevelFilter::min_level(default_severities(), "loud").err().unwrap();
+		assert_eq!(
+			error.to_string(),
+			"Unknown level `loud`, known levels are trace, debug, info, warn, error, fatal"
+		);
+	}
+}
@@ -1,157 +1,302 @@
+use crate::clef;
+use crate::compiled_template::{Field, Fields};
+use crate::input::Source;
 use crate::log_settings::LogSettings;
-use crate::time::try_convert_timestamp_to_readable;
-use handlebars::Handlebars;
+use crate::stack_trace;
+use crate::template::Templates;
+use crate::time::{self, try_convert_timestamp_to_readable};
+use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
+use serde::Serialize;
+use serde::ser::{SerializeMap, Serializer};
 use serde_json::{Map, Value};
-use std::borrow::ToOwned;
-use std::collections::BTreeMap;
-use std::io::Write;
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::io::{self, Write};
 use yansi::Paint;
 
+/// Information about a log entry that is not part of its fields.
+#[derive(Default)]
+pub struct EntryContext<'a> {
+	pub prefix: Option<&'a str>,
+	pub source: Option<&'a Source>,
+}
+
 pub fn print_log_line(
 	out: &mut dyn Write,
-	maybe_prefix: Option<&str>,
-	log_entry: &Map<String, Value>,
+	context: &EntryContext,
+	flat_entry: &FlatEntry,
 	log_settings: &LogSettings,
-	handlebars: &Handlebars<'static>,
-) {
-	let string_log_entry = flatten_json(log_entry, "");
-	let level = {
-		let level = get_string_value_or_default(&string_log_entry, &log_settings.level_keys, "unknown");
-		log_settings.level_map.get(&level).cloned().unwrap_or(level)
-	};
-
-	let trimmed_prefix = maybe_prefix.map(|p| p.trim()).unwrap_or_else(|| "").to_string();
-	let mut message = get_string_value_or_default(&string_log_entry, &log_settings.message_keys, "");
-	let timestamp = try_convert_timestamp_to_readable(get_string_value_or_default(&string_log_entry, &log_settings.time_keys, ""));
+	templates: &Templates,
+) -> io::Result<()> {
+	let log_entry = flat_entry.log_entry;
+	let level = resolve_level(flat_entry, log_settings).unwrap_or(Cow::Borrowed("unknown"));
+
+	let trimmed_prefix = context.prefix.map(|p| p.trim()).unwrap_or("");
+	let mut message = flat_entry.get_string_value(&log_settings.message_keys).unwrap_or_default();
+	let timestamp = try_convert_timestamp_to_readable(
+		flat_entry.get_string_value(&log_settings.time_keys).unwrap_or_default().into_owned(),
+		log_settings.epoch_unit,
+	);
+
+	// Messages of CLEF events are rendered from the message template, unless another message key matched
+	if let Some(template) = log_entry.get("@mt").and_then(Value::as_str)
+		&& (message.is_empty() || message == template)
+	{
+		message = Cow::Owned(clef::render_message_template(template, log_entry));
+	}
 
 	if let Some(message_template) = &log_settings.substitution
 		&& let Some(templated_message) = message_template.apply(&message, log_entry)
 	{
-		message = templated_message;
+		message = Cow::Owned(templated_message);
 	}
 
-	let mut handle_bar_input: Map<String, Value> = log_entry.clone();
-	handle_bar_input.insert("fblog_timestamp".to_string(), Value::String(timestamp));
-	handle_bar_input.insert("fblog_level".to_string(), Value::String(level));
-	handle_bar_input.insert("fblog_message".to_string(), Value::String(message));
-	handle_bar_input.insert("fblog_prefix".to_string(), Value::String(trimmed_prefix));
+	let template_data = TemplateData {
+		log_entry,
+		variables: [
+			("fblog_timestamp", Some(&timestamp)),
+			("fblog_level", Some(&level)),
+			("fblog_message", Some(&message)),
+			("fblog_prefix", Some(trimmed_prefix)),
+			("fblog_source", context.source.map(|source| source.name.as_str())),
+			("fblog_source_tag", context.source.and_then(|source| source.tag.as_deref())),
+		],
+	};
 
-	let write_result = match handlebars.render("main_line", &handle_bar_input) {
-		Ok(string) => writeln!(out, "{string}"),
-		Err(e) => writeln!(out, "{} Failed to process line: {}", "??? >".red().bold(), e),
+	match templates.render_main_line(&template_data) {
+		Ok(string) => writeln!(out, "{string}")?,
+		Err(e) => writeln!(out, "{} Failed to process line: {}", "??? >".red().bold(), e)?,
 	};
 
-	if write_result.is_err() {
-		// Output end reached
-		std::process::exit(14);
+	for stack_trace_key in &log_settings.stack_trace_keys {
+		if let Some(stack_trace) = flat_entry.get(stack_trace_key) {
+			stack_trace::write_stack_trace(out, &stack_trace, &log_settings.collapsed_packages)?;
+		}
 	}
 
 	if log_settings.dump_all {
-		let mut all_values: Vec<String> = string_log_entry
-			.keys()
-			.map(ToOwned::to_owned)
-			.filter(|v| !log_settings.excluded_values.contains(v))
+		let mut all_values: Vec<(&String, &String)> = flat_entry
+			.flattened()
+			.iter()
+			.filter(|(key, _)| !log_settings.excluded_values.contains(key) && !log_settings.stack_trace_keys.contains(key))
 			.collect();
 		all_values.sort();
-		write_additional_values(out, &string_log_entry, &all_values, handlebars);
+		for (key, value) in all_values {
+			write_additional_value(out, key, value, templates)?;
+		}
+		Ok(())
 	} else {
-		write_additional_values(out, &string_log_entry, &log_settings.additional_values, handlebars);
+		write_additional_values(out, flat_entry, log_settings, templates)
+	}
+}
+
+pub fn get_parsed_timestamp(flat_entry: &FlatEntry, log_settings: &LogSettings) -> Option<DateTime<Utc>> {
+	let timestamp = flat_entry.get_string_value(&log_settings.time_keys)?;
+	time::parse_timestamp(&timestamp, log_settings.epoch_unit)
+}
+
+/// The level of a log entry after applying the level map. CLEF events without a level are `info`.
+pub fn resolve_level<'a>(flat_entry: &'a FlatEntry, log_settings: &'a LogSettings) -> Option<Cow<'a, str>> {
+	let Some(level) = flat_entry.get_string_value(&log_settings.level_keys) else {
+		return clef::is_clef(flat_entry.log_entry).then_some(Cow::Borrowed(clef::DEFAULT_LEVEL));
+	};
+	match log_settings.level_map.get(level.as_ref()) {
+		Some(mapped_level) => Some(Cow::Borrowed(mapped_level.as_str())),
+		None => Some(level),
+	}
+}
+
+/// The fields of a log entry and the `fblog_*` variables for the templates.
+///
+/// The variables are layered on top of the borrowed entry, they replace fields with the same name.
+struct TemplateData<'a> {
+	log_entry: &'a Map<String, Value>,
+	variables: [(&'static str, Option<&'a str>); 6],
+}
+
+impl Serialize for TemplateData<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut map = serializer.serialize_map(None)?;
+		for (key, value) in self.log_entry {
+			if !self.variables.iter().any(|(name, variable)| variable.is_some() && name == key) {
+				map.serialize_entry(key, value)?;
+			}
+		}
+		for (name, variable) in &self.variables {
+			if let Some(variable) = variable {
+				map.serialize_entry(name, variable)?;
+			}
+		}
+		map.end()
+	}
+}
+
+impl Fields for TemplateData<'_> {
+	fn field(&self, name: &str) -> Option<Field<'_>> {
+		match self.variables.iter().find(|(variable_name, _)| *variable_name == name) {
+			Some((_, Some(variable))) => Some(Field::Text(variable)),
+			_ => self.log_entry.get(name).map(Field::Json),
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct AdditionalValue<'a> {
+	key: &'a str,
+	value: &'a str,
+}
+
+impl Fields for AdditionalValue<'_> {
+	fn field(&self, name: &str) -> Option<Field<'_>> {
+		match name {
+			"key" => Some(Field::Text(self.key)),
+			"value" => Some(Field::Text(self.value)),
+			_ => None,
+		}
+	}
+}
+
+/// A log entry whose nested objects and arrays are flattened into fields like `nested > key` or `array[0]`.
+///
+/// Top-level fields are read directly from the entry, the flattened fields are only computed when needed.
+/// It is created once per log entry and shared by the filters and the output.
+pub struct FlatEntry<'a> {
+	log_entry: &'a Map<String, Value>,
+	flattened: OnceCell<IndexMap<String, String>>,
+}
+
+impl<'a> FlatEntry<'a> {
+	pub fn new(log_entry: &'a Map<String, Value>) -> FlatEntry<'a> {
+		FlatEntry {
+			log_entry,
+			flattened: OnceCell::new(),
+		}
+	}
+
+	fn flattened(&self) -> &IndexMap<String, String> {
+		self.flattened.get_or_init(|| flatten_json(self.log_entry, ""))
+	}
+
+	fn get(&self, key: &str) -> Option<Cow<'_, str>> {
+		match self.log_entry.get(key).and_then(scalar_to_string) {
+			Some(value) => Some(value),
+			None if is_flattened_key(key) => self.flattened().get(key).map(|value| Cow::Borrowed(value.as_str())),
+			None => None,
+		}
+	}
+
+	fn get_string_value(&self, keys: &[String]) -> Option<Cow<'_, str>> {
+		keys.iter().find_map(|key| self.get(key))
+	}
+}
+
+fn is_flattened_key(key: &str) -> bool {
+	key.contains(" > ") || key.contains('[')
+}
+
+fn scalar_to_string(value: &Value) -> Option<Cow<'_, str>> {
+	match value {
+		Value::String(string_value) => Some(Cow::Borrowed(string_value)),
+		Value::Bool(bool_value) => Some(Cow::Owned(bool_value.to_string())),
+		Value::Number(number_value) => Some(Cow::Owned(number_value.to_string())),
+		_ => None,
 	}
 }
 
 fn flatten_json(log_entry: &Map<String, Value>, prefix: &str) -> IndexMap<String, String> {
 	let mut flattened_json: IndexMap<String, String> = IndexMap::new();
 	for (key, value) in log_entry {
-		match value {
-			Value::String(string_value) => {
-				flattened_json.insert(format!("{prefix}{key}"), string_value.to_string());
-			}
-			Value::Bool(bool_value) => {
-				flattened_json.insert(format!("{prefix}{key}"), bool_value.to_string());
-			}
-			Value::Number(number_value) => {
-				flattened_json.insert(format!("{prefix}{key}"), number_value.to_string());
-			}
-			Value::Array(array_values) => {
-				for (index, array_value) in array_values.iter().enumerate() {
-					let key = format!("{}[{}]", key, index);
-
-					match array_value {
-						Value::Array(array_values) => {
-							flatten_array(&key, prefix, array_values, &mut flattened_json);
-						}
-						Value::Object(nested_entry) => {
-							flattened_json.extend(flatten_json(nested_entry, &format!("{prefix}{key} > ")));
-						}
-						_ => {
-							flattened_json.insert(format!("{prefix}{key}"), array_value.to_string());
-						}
-					};
-				}
-			}
-			Value::Object(nested_entry) => {
-				flattened_json.extend(flatten_json(nested_entry, &format!("{prefix}{key} > ")));
-			}
-			Value::Null => {}
-		};
+		flatten_value(&format!("{prefix}{key}"), value, &mut flattened_json);
 	}
 	flattened_json
 }
 
-fn flatten_array(key: &str, prefix: &str, array_values: &[Value], flattened_json: &mut IndexMap<String, String>) {
-	for (index, array_value) in array_values.iter().enumerate() {
-		let key = format!("{}[{}]", key, index + 1); // lua tables indexes start with 1
-
-		match array_value {
-			Value::Array(nested_array_values) => flatten_array(&key, prefix, nested_array_values, flattened_json),
-			Value::Object(nested_entry) => {
-				flattened_json.extend(flatten_json(nested_entry, &format!("{prefix}{key} > ")));
+fn flatten_value(key: &str, value: &Value, flattened_json: &mut IndexMap<String, String>) {
+	match value {
+		Value::Array(array_values) => {
+			for (index, array_value) in array_values.iter().enumerate() {
+				flatten_array_value(&format!("{key}[{index}]"), array_value, flattened_json);
 			}
-			_ => {
-				flattened_json.insert(format!("{prefix}{key}"), array_value.to_string());
+		}
+		Value::Object(nested_entry) => {
+			flattened_json.extend(flatten_json(nested_entry, &format!("{key} > ")));
+		}
+		Value::Null => {}
+		_ => {
+			if let Some(string_value) = scalar_to_string(value) {
+				flattened_json.insert(key.to_string(), string_value.into_owned());
 			}
-		};
+		}
 	}
 }
 
-fn get_string_value(value: &IndexMap<String, String>, keys: &[String]) -> Option<String> {
-	keys
-		.iter()
-		.fold(None::<String>, |maybe_match, key| maybe_match.or_else(|| value.get(key).map(ToOwned::to_owned)))
-}
-
-fn get_string_value_or_default(value: &IndexMap<String, String>, keys: &[String], default: &str) -> String {
-	get_string_value(value, keys).unwrap_or_else(|| default.to_string())
+fn flatten_array_value(key: &str, array_value: &Value, flattened_json: &mut IndexMap<String, String>) {
+	match array_value {
+		Value::Array(nested_array_values) => {
+			for (index, nested_array_value) in nested_array_values.iter().enumerate() {
+				// lua tables indexes start with 1
+				flatten_array_value(&format!("{key}[{}]", index + 1), nested_array_value, flattened_json);
+			}
+		}
+		Value::Object(nested_entry) => {
+			flattened_json.extend(flatten_json(nested_entry, &format!("{key} > ")));
+		}
+		_ => {
+			flattened_json.insert(key.to_string(), array_value.to_string());
+		}
+	}
 }
 
-fn write_additional_values(out: &mut dyn Write, log_entry: &IndexMap<String, String>, additional_values: &[String], handlebars: &Handlebars<'static>) {
-	for additional_value_prefix in additional_values {
-		for additional_value in log_entry.keys().filter(|k| {
-			*k == additional_value_prefix || k.starts_with(&format!("{additional_value_prefix} > ")) || k.starts_with(&format!("{additional_value_prefix}["))
-		}) {
-			if let Some(value) = get_string_value(log_entry, &[additional_value.to_string()]) {
-				let mut variables: BTreeMap<String, String> = BTreeMap::new();
-				variables.insert("key".to_string(), additional_value.to_string());
-				variables.insert("value".to_string(), value.to_string());
-
-				let write_result = match handlebars.render("additional_value", &variables) {
-					Ok(string) => writeln!(out, "{string}"),
-					Err(e) => writeln!(out, "{} Failed to process additional value: {}", "   ??? >".red().bold(), e),
-				};
-				if write_result.is_err() {
-					// Output end reached
-					std::process::exit(14);
+/// Writes every flattened field of the given names, e.g. `nested` also writes `nested > key`.
+/// Stack traces are skipped, they are already written below the main line.
+fn write_additional_values(out: &mut dyn Write, flat_entry: &FlatEntry, log_settings: &LogSettings, templates: &Templates) -> io::Result<()> {
+	let is_stack_trace = |key: &String| log_settings.stack_trace_keys.contains(key);
+	for additional_value_prefix in log_settings.additional_values.iter().filter(|key| !is_stack_trace(key)) {
+		match flat_entry.log_entry.get(additional_value_prefix) {
+			Some(value) => {
+				if let Some(string_value) = scalar_to_string(value) {
+					write_additional_value(out, additional_value_prefix, &string_value, templates)?;
+				} else {
+					let mut flattened_value = IndexMap::new();
+					flatten_value(additional_value_prefix, value, &mut flattened_value);
+					for (key, value) in flattened_value.iter().filter(|(key, _)| !is_stack_trace(key)) {
+						write_additional_value(out, key, value, templates)?;
+					}
+				}
+			}
+			// Nested names like `nested > key` and top-level fields whose name contains ` > ` need the flattened fields.
+			None if is_flattened_key(additional_value_prefix) || flat_entry.log_entry.keys().any(|key| key.starts_with(additional_value_prefix.as_str())) => {
+				let nested_prefix = format!("{additional_value_prefix} > ");
+				let array_prefix = format!("{additional_value_prefix}[");
+				for (key, value) in flat_entry
+					.flattened()
+					.iter()
+					.filter(|(key, _)| *key == additional_value_prefix || key.starts_with(&nested_prefix) || key.starts_with(&array_prefix))
+					.filter(|(key, _)| !is_stack_trace(key))
+				{
+					write_additional_value(out, key, value, templates)?;
 				}
 			}
+			None => (),
 		}
 	}
+	Ok(())
+}
+
+fn write_additional_value(out: &mut dyn Write, key: &str, value: &str, templates: &Templates) -> io::Result<()> {
+	match templates.render_additional_value(&AdditionalValue { key, value }) {
+		Ok(string) => writeln!(out, "{string}"),
+		Err(e) => writeln!(out, "{} Failed to process additional value: {}", "   ??? >".red().bold(), e),
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::substitution::Substitution;
 	use crate::template;
+	use std::collections::BTreeMap;
 
 	fn without_style(styled: &str) -> String {
 		use regex::Regex;
@@ -159,11 +304,18 @@ mod tests {
 		regex.replace_all(styled, "").into_owned()
 	}
 
-	fn fblog_handlebar_registry_default_format() -> Handlebars<'static> {
+	fn templates_default_format() -> Templates {
 		let main_line_format = template::DEFAULT_MAIN_LINE_FORMAT.to_string();
 		let additional_value_format = template::DEFAULT_ADDITIONAL_VALUE_FORMAT.to_string();
 
-		template::fblog_handlebar_registry(main_line_format, additional_value_format)
+		Templates::new(main_line_format, additional_value_format, Substitution::default())
+	}
+
+	fn with_prefix(prefix: &str) -> EntryContext<'_> {
+		EntryContext {
+			prefix: Some(prefix),
+			..Default::default()
+		}
 	}
 
 	fn out_to_string(out: Vec<u8>) -> String {
@@ -173,7 +325,7 @@ mod tests {
 
 	#[test]
 	fn write_log_entry() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let log_settings = LogSettings::new_default_settings();
 		let mut out: Vec<u8> = Vec::new();
 		let mut log_entry: Map<String, Value> = Map::new();
@@ -182,14 +334,14 @@ mod tests {
 		log_entry.insert("process".to_string(), Value::String("rust".to_string()));
 		log_entry.insert("level".to_string(), Value::String("info".to_string()));
 
-		print_log_line(&mut out, None, &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(out_to_string(out), "2017-07-06T15:21:16  INFO: something happened\n");
 	}
 
 	#[test]
 	fn write_log_entry_with_mapped_level() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let mut log_settings = LogSettings::new_default_settings();
 		log_settings.level_map = BTreeMap::from([("30".to_string(), "info".to_string())]);
 
@@ -200,14 +352,14 @@ mod tests {
 		log_entry.insert("process".to_string(), Value::String("rust".to_string()));
 		log_entry.insert("level".to_string(), Value::String("30".to_string()));
 
-		print_log_line(&mut out, None, &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(out_to_string(out), "2017-07-06T15:21:16  INFO: something happened\n");
 	}
 
 	#[test]
 	fn write_log_entry_with_prefix() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let log_settings = LogSettings::new_default_settings();
 		let mut out: Vec<u8> = Vec::new();
 		let prefix = "abc";
@@ -217,14 +369,14 @@ mod tests {
 		log_entry.insert("process".to_string(), Value::String("rust".to_string()));
 		log_entry.insert("level".to_string(), Value::String("info".to_string()));
 
-		print_log_line(&mut out, Some(prefix), &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &with_prefix(prefix), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(out_to_string(out), "2017-07-06T15:21:16  INFO: abc something happened\n");
 	}
 
 	#[test]
 	fn write_log_entry_with_additional_field() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let mut out: Vec<u8> = Vec::new();
 		let mut log_entry: Map<String, Value> = Map::new();
 		log_entry.insert("message".to_string(), Value::String("something happened".to_string()));
@@ -235,7 +387,7 @@ mod tests {
 		let mut log_settings = LogSettings::new_default_settings();
 		log_settings.add_additional_values(vec!["process".to_string(), "fu".to_string()]);
 
-		print_log_line(&mut out, None, &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(
 			out_to_string(out),
@@ -249,7 +401,7 @@ mod tests {
 
 	#[test]
 	fn write_log_entry_with_array() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let mut out: Vec<u8> = Vec::new();
 		let mut log_entry: Map<String, Value> = Map::new();
 		log_entry.insert("message".to_string(), Value::String("something happened".to_string()));
@@ -260,7 +412,7 @@ mod tests {
 		let mut log_settings = LogSettings::new_default_settings();
 		log_settings.add_additional_values(vec!["process".to_string(), "fu".to_string()]);
 
-		print_log_line(&mut out, None, &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(
 			out_to_string(out),
@@ -273,7 +425,7 @@ mod tests {
 	}
 	#[test]
 	fn write_log_entry_with_nested() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let mut out: Vec<u8> = Vec::new();
 		let mut fu: Map<String, Value> = Map::new();
 		fu.insert("test".to_string(), Value::String("hello".to_string()));
@@ -287,7 +439,7 @@ mod tests {
 		let mut log_settings = LogSettings::new_default_settings();
 		log_settings.add_additional_values(vec!["process".to_string(), "fu".to_string()]);
 
-		print_log_line(&mut out, None, &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(
 			out_to_string(out),
@@ -301,7 +453,7 @@ mod tests {
 
 	#[test]
 	fn write_log_entry_with_additional_field_and_prefix() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let mut out: Vec<u8> = Vec::new();
 		let mut log_entry: Map<String, Value> = Map::new();
 		log_entry.insert("message".to_string(), Value::String("something happened".to_string()));
@@ -314,7 +466,7 @@ mod tests {
 		let mut log_settings = LogSettings::new_default_settings();
 		log_settings.add_additional_values(vec!["process".to_string(), "fu".to_string()]);
 
-		print_log_line(&mut out, Some(prefix), &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &with_prefix(prefix), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(
 			out_to_string(out),
@@ -328,7 +480,7 @@ mod tests {
 
 	#[test]
 	fn write_log_entry_dump_all() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let mut out: Vec<u8> = Vec::new();
 		let mut log_entry: Map<String, Value> = Map::new();
 		log_entry.insert("message".to_string(), Value::String("something happened".to_string()));
@@ -339,7 +491,7 @@ mod tests {
 
 		let mut log_settings = LogSettings::new_default_settings();
 		log_settings.dump_all = true;
-		print_log_line(&mut out, None, &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(
 			out_to_string(out),
@@ -356,7 +508,7 @@ mod tests {
 
 	#[test]
 	fn write_log_entry_with_exotic_fields() {
-		let handlebars = fblog_handlebar_registry_default_format();
+		let templates = templates_default_format();
 		let mut log_settings = LogSettings::new_default_settings();
 		let mut out: Vec<u8> = Vec::new();
 		let mut log_entry: Map<String, Value> = Map::new();
@@ -371,8 +523,131 @@ mod tests {
 		log_settings.add_time_keys(vec!["moep".to_string()]);
 		log_settings.add_level_keys(vec!["hugo".to_string()]);
 
-		print_log_line(&mut out, None, &log_entry, &log_settings, &handlebars);
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
 
 		assert_eq!(out_to_string(out), "               moep  HUGO: rust\n");
 	}
+
+	#[test]
+	fn write_log_entry_with_source_tag() {
+		let templates = templates_default_format();
+		let log_settings = LogSettings::new_default_settings();
+		let mut out: Vec<u8> = Vec::new();
+		let mut log_entry: Map<String, Value> = Map::new();
+		log_entry.insert("message".to_string(), Value::String("something happened".to_string()));
+		log_entry.insert("time".to_string(), Value::String("2017-07-06T15:21:16".to_string()));
+		log_entry.insert("level".to_string(), Value::String("info".to_string()));
+
+		let source = Source::with_tag("logs/api.log", 0);
+		let context = EntryContext {
+			source: Some(&source),
+			..Default::default()
+		};
+		print_log_line(&mut out, &context, &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
+
+		assert_eq!(out_to_string(out), "api.log 2017-07-06T15:21:16  INFO: something happened\n");
+	}
+
+	#[test]
+	fn write_log_entry_with_nested_keys() {
+		let templates = Templates::new(
+			"{{fblog_level}} {{fblog_message}} {{fblog_prefix}}".to_string(),
+			template::DEFAULT_ADDITIONAL_VALUE_FORMAT.to_string(),
+			Substitution::default(),
+		);
+		let mut log_settings = LogSettings::new_default_settings();
+		log_settings.add_message_keys(vec!["nested > items[1]".to_string()]);
+		log_settings.add_level_keys(vec!["nested > severity".to_string()]);
+		log_settings.additional_values = vec!["nested > items".to_string()];
+		let mut out: Vec<u8> = Vec::new();
+		let log_entry = serde_json::json!({"nested": {"severity": "warn", "items": ["first", "second"]}, "fblog_prefix": "own"});
+		let Value::Object(log_entry) = log_entry else { panic!("not an object") };
+
+		print_log_line(&mut out, &with_prefix("pod "), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
+
+		assert_eq!(
+			out_to_string(out),
+			"warn \"second\" pod\n        nested > items[0]: \"first\"\n        nested > items[1]: \"second\"\n"
+		);
+	}
+
+	#[test]
+	fn write_clef_log_entry() {
+		let templates = templates_default_format();
+		let mut log_settings = LogSettings::new_default_settings();
+		log_settings.apply_preset(crate::preset::resolve("clef", &BTreeMap::new()).unwrap());
+		let mut out: Vec<u8> = Vec::new();
+		let log_entry = serde_json::json!({
+			"@t": "2017-07-06T15:21:16.0000000Z",
+			"@mt": "Saving {Count} orders of {@User}",
+			"Count": 3,
+			"User": {"Name": "Ann"},
+			"@x": "System.Exception: failed\n   at App.Main()"
+		});
+		let Value::Object(log_entry) = log_entry else { panic!("not an object") };
+
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
+
+		assert_eq!(
+			out_to_string(out),
+			"2017-07-06T15:21:16  INFO: Saving 3 orders of { Name: \"Ann\" }\n    System.Exception: failed\n       at App.Main()\n"
+		);
+	}
+
+	#[test]
+	fn resolve_clef_default_level_without_preset() {
+		let log_settings = LogSettings::new_default_settings();
+		let log_entry = serde_json::json!({"@t": "2017-07-06T15:21:16.0000000Z", "@mt": "Started"});
+		let Value::Object(log_entry) = log_entry else { panic!("not an object") };
+
+		assert_eq!(resolve_level(&FlatEntry::new(&log_entry), &log_settings).as_deref(), Some("info"));
+	}
+
+	#[test]
+	fn write_log_entry_with_substitute_helper() {
+		let templates = Templates::new(
+			"{{fblog_message}} {{substitute detail}}".to_string(),
+			template::DEFAULT_ADDITIONAL_VALUE_FORMAT.to_string(),
+			Substitution::default(),
+		);
+		let log_settings = LogSettings::new_default_settings();
+		let mut out: Vec<u8> = Vec::new();
+		let log_entry = serde_json::json!({"message": "done", "detail": "took {duration:.1} ms", "duration": 2.26});
+		let Value::Object(log_entry) = log_entry else { panic!("not an object") };
+
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
+
+		assert_eq!(out_to_string(out), "done took 2.3 ms\n");
+	}
+
+	#[test]
+	fn write_log_entry_with_stack_trace() {
+		let templates = templates_default_format();
+		let mut log_settings = LogSettings::new_default_settings();
+		log_settings.dump_all = true;
+		let mut out: Vec<u8> = Vec::new();
+		let log_entry = serde_json::json!({
+			"message": "request failed",
+			"time": "2017-07-06T15:21:16",
+			"level": "error",
+			"error": {"type": "IOException", "stack_trace": "java.io.IOException: disk full\n\tat com.acme.Store.write(Store.java:12)\n\tat java.lang.Thread.run(Thread.java:748)"}
+		});
+		let Value::Object(log_entry) = log_entry else { panic!("not an object") };
+
+		print_log_line(&mut out, &EntryContext::default(), &FlatEntry::new(&log_entry), &log_settings, &templates).unwrap();
+
+		assert_eq!(
+			out_to_string(out),
+			"\
+2017-07-06T15:21:16 ERROR: request failed
+    java.io.IOException: disk full
+        at com.acme.Store.write(Store.java:12)
+        ... 1 frame omitted
+             error > type: IOException
+                    level: error
+                  message: request failed
+                     time: 2017-07-06T15:21:16
+"
+		);
+	}
 }
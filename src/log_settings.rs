@@ -1,7 +1,37 @@
+use regex::Regex;
 use std::collections::BTreeMap;
 
+use crate::encoding::Encoding;
+use crate::level::LevelFilter;
+use crate::preset::Preset;
+use crate::time::EpochUnit;
+use crate::time_range::TimeRange;
 use crate::{config::Config, substitution::Substitution};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+	/// One json log entry per line
+	Json,
+	/// json log entries independent of line breaks
+	JsonStream,
+	/// One logfmt log entry per line
+	Logfmt,
+	/// One syslog (RFC 5424 or RFC 3164) log entry per line
+	Syslog,
+}
+
+impl InputFormat {
+	pub fn from_name(name: &str) -> Option<InputFormat> {
+		match name {
+			"json" => Some(InputFormat::Json),
+			"json-stream" => Some(InputFormat::JsonStream),
+			"logfmt" => Some(InputFormat::Logfmt),
+			"syslog" => Some(InputFormat::Syslog),
+			_ => None,
+		}
+	}
+}
+
 pub struct LogSettings {
 	pub message_keys: Vec<String>,
 	pub time_keys: Vec<String>,
@@ -9,10 +39,24 @@ pub struct LogSettings {
 	pub level_map: BTreeMap<String, String>,
 	pub additional_values: Vec<String>,
 	pub excluded_values: Vec<String>,
+	pub stack_trace_keys: Vec<String>,
+	pub collapsed_packages: Vec<String>,
 	pub dump_all: bool,
 	pub with_prefix: bool,
 	pub print_lua: bool,
 	pub substitution: Option<Substitution>,
+	pub input_format: InputFormat,
+	pub explode_path: Option<String>,
+	pub parse_regexes: Vec<Regex>,
+	pub embedded_json_keys: Vec<String>,
+	pub prefix_regex: Option<Regex>,
+	pub continuation_regex: Option<Regex>,
+	/// The input can be idle for a while, like stdin or a followed file.
+	pub idle_input: bool,
+	pub encoding: Encoding,
+	pub time_range: Option<TimeRange>,
+	pub level_filter: Option<LevelFilter>,
+	pub epoch_unit: EpochUnit,
 }
 
 impl LogSettings {
@@ -24,10 +68,23 @@ impl LogSettings {
 			level_map: config.level_map.clone(),
 			additional_values: config.always_print_fields.clone(),
 			excluded_values: config.dump_all_exclude.clone(),
+			stack_trace_keys: config.stack_trace_keys.clone(),
+			collapsed_packages: config.collapsed_packages.clone(),
 			dump_all: false,
 			with_prefix: false,
 			print_lua: false,
 			substitution: None,
+			input_format: InputFormat::Json,
+			explode_path: None,
+			parse_regexes: vec![],
+			embedded_json_keys: vec![],
+			prefix_regex: None,
+			continuation_regex: None,
+			idle_input: false,
+			encoding: Encoding::Utf8,
+			time_range: None,
+			level_filter: None,
+			epoch_unit: EpochUnit::Auto,
 		}
 	}
 
@@ -37,6 +94,30 @@ impl LogSettings {
 		LogSettings::from_config(&default_config)
 	}
 
+	/// The keys of the preset are tried after the keys of the config file, which also override its level map.
+	/// Keys set on the command line are added afterwards and are tried first.
+	pub fn apply_preset(&mut self, preset: Preset) {
+		if let Some(message_keys) = preset.message_keys {
+			append_missing(&mut self.message_keys, message_keys);
+		}
+		if let Some(time_keys) = preset.time_keys {
+			append_missing(&mut self.time_keys, time_keys);
+		}
+		if let Some(level_keys) = preset.level_keys {
+			append_missing(&mut self.level_keys, level_keys);
+		}
+		if let Some(mut level_map) = preset.level_map {
+			level_map.append(&mut self.level_map);
+			self.level_map = level_map;
+		}
+		if let Some(epoch_unit) = preset.epoch_unit {
+			self.epoch_unit = epoch_unit;
+		}
+		if let Some(excluded_values) = preset.excluded_values {
+			self.add_excluded_values(excluded_values);
+		}
+	}
+
 	pub fn add_additional_values(&mut self, mut additional_values: Vec<String>) {
 		self.additional_values.append(&mut additional_values);
 	}
@@ -64,7 +145,51 @@ impl LogSettings {
 		self.excluded_values.append(&mut excluded_values);
 	}
 
+	pub fn add_stack_trace_keys(&mut self, mut stack_trace_keys: Vec<String>) {
+		self.stack_trace_keys.append(&mut stack_trace_keys);
+	}
+
+	pub fn add_parse_regex(&mut self, parse_regex: Regex) {
+		self.parse_regexes.push(parse_regex);
+	}
+
+	pub fn add_embedded_json_keys(&mut self, mut embedded_json_keys: Vec<String>) {
+		self.embedded_json_keys.append(&mut embedded_json_keys);
+	}
+
 	pub fn add_substitution(&mut self, message_template: Substitution) {
 		self.substitution = Some(message_template)
 	}
 }
+
+fn append_missing(keys: &mut Vec<String>, new_keys: Vec<String>) {
+	for key in new_keys {
+		if !keys.contains(&key) {
+			keys.push(key);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn config_and_cli_take_precedence_over_preset() {
+		let mut config = Config::new();
+		config.message_keys = vec!["text".to_string()];
+		config.level_map = BTreeMap::from([("30".to_string(), "warn".to_string())]);
+		let mut log_settings = LogSettings::from_config(&config);
+
+		log_settings.apply_preset(crate::preset::resolve("pino", &BTreeMap::new()).unwrap());
+		assert_eq!(log_settings.message_keys, vec!["text", "msg"]);
+		assert_eq!(log_settings.level_map["30"], "warn");
+		assert_eq!(log_settings.level_map["40"], "warn");
+		assert_eq!(log_settings.level_map["50"], "error");
+
+		log_settings.add_message_keys(vec!["cli".to_string()]);
+		log_settings.add_level_map(vec![("50".to_string(), "fatal".to_string())]);
+		assert_eq!(log_settings.message_keys, vec!["cli", "text", "msg"]);
+		assert_eq!(log_settings.level_map["50"], "fatal");
+	}
+}
@@ -0,0 +1,150 @@
+use serde_json::{Map, Value};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Parses a logfmt line (`ts=... level=info msg="x" user=42`) into a log entry.
+///
+/// Values are kept as strings, keys without a value are set to `true`.
+/// Returns `None` for lines that are not valid logfmt.
+pub fn parse(line: &str) -> Option<Map<String, Value>> {
+	parse_counting_pairs(line).map(|(log_entry, _, _)| log_entry)
+}
+
+/// Keys of which a detected logfmt line needs at least one.
+static DETECTION_KEYS: &[&str] = &["msg", "message", "level", "lvl", "severity", "ts", "time", "timestamp"];
+
+/// Like [`parse`], but only accepts lines where most words are `key=value` pairs and one of them is a message,
+/// level or time. Used to detect logfmt lines without misinterpreting plain text like
+/// `Connecting to db host=localhost port=5432` as bare keys.
+pub fn parse_detected(line: &str) -> Option<Map<String, Value>> {
+	match parse_counting_pairs(line) {
+		Some((log_entry, pairs, tokens)) if pairs * 2 > tokens && DETECTION_KEYS.iter().any(|key| log_entry.get(*key).is_some_and(Value::is_string)) => {
+			Some(log_entry)
+		}
+		_ => None,
+	}
+}
+
+/// Returns the log entry with the number of `key=value` pairs and of all words.
+fn parse_counting_pairs(line: &str) -> Option<(Map<String, Value>, usize, usize)> {
+	let mut log_entry = Map::new();
+	let mut pairs = 0;
+	let mut tokens = 0;
+	let mut chars = line.chars().peekable();
+
+	loop {
+		while chars.next_if(|c| c.is_whitespace()).is_some() {}
+		if chars.peek().is_none() {
+			break;
+		}
+
+		let mut key = String::new();
+		while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+			if c == '"' {
+				return None;
+			}
+			key.push(c);
+		}
+		if key.is_empty() {
+			return None;
+		}
+		tokens += 1;
+
+		if chars.next_if_eq(&'=').is_some() {
+			let value = if chars.next_if_eq(&'"').is_some() {
+				let value = parse_quoted(&mut chars)?;
+				if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+					return None;
+				}
+				value
+			} else {
+				let mut value = String::new();
+				while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+					if c == '"' {
+						return None;
+					}
+					value.push(c);
+				}
+				value
+			};
+			log_entry.insert(key, Value::String(value));
+			pairs += 1;
+		} else {
+			log_entry.insert(key, Value::Bool(true));
+		}
+	}
+
+	if log_entry.is_empty() { None } else { Some((log_entry, pairs, tokens)) }
+}
+
+/// Reads a quoted value after the opening quote up to and including the closing quote.
+fn parse_quoted(chars: &mut Peekable<Chars>) -> Option<String> {
+	let mut value = String::new();
+	loop {
+		match chars.next()? {
+			'"' => return Some(value),
+			'\\' => match chars.next()? {
+				'n' => value.push('\n'),
+				'r' => value.push('\r'),
+				't' => value.push('\t'),
+				'"' => value.push('"'),
+				'\\' => value.push('\\'),
+				other => {
+					value.push('\\');
+					value.push(other);
+				}
+			},
+			c => value.push(c),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn parsed(line: &str) -> Value {
+		Value::Object(parse(line).unwrap())
+	}
+
+	#[test]
+	fn simple_pairs() {
+		assert_eq!(
+			parsed("ts=2024-05-21T11:21:29Z level=info user=42"),
+			json!({"ts": "2024-05-21T11:21:29Z", "level": "info", "user": "42"})
+		);
+	}
+
+	#[test]
+	fn quoted_values() {
+		assert_eq!(
+			parsed(r#"msg="hello world" path="C:\\temp" quote="say \"hi\"" empty="" tab="a\tb""#),
+			json!({"msg": "hello world", "path": "C:\\temp", "quote": "say \"hi\"", "empty": "", "tab": "a\tb"})
+		);
+	}
+
+	#[test]
+	fn bare_keys_and_empty_values() {
+		assert_eq!(parsed("level=debug cached retry="), json!({"level": "debug", "cached": true, "retry": ""}));
+	}
+
+	#[test]
+	fn invalid_lines() {
+		assert!(parse(r#"msg="unterminated"#).is_none());
+		assert!(parse(r#"msg="a"b"#).is_none());
+		assert!(parse("=value").is_none());
+		assert!(parse("").is_none());
+	}
+
+	#[test]
+	fn detect_logfmt() {
+		assert!(parse_detected("level=info msg=started").is_some());
+		assert!(parse_detected("ts=2024-05-21T11:21:29Z cached path=/health").is_some());
+		assert!(parse_detected("Starting server on port 8080").is_none());
+		assert!(parse_detected("Connecting to db host=localhost port=5432").is_none());
+		assert!(parse_detected("host=localhost port=5432").is_none());
+		assert!(parse_detected("level=info started server on port").is_none());
+		assert!(parse("Starting server on port 8080").is_some());
+	}
+}
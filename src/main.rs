@@ -1,23 +1,53 @@
-use std::io;
+use std::io::{self, Read, Write};
 
 #[cfg(test)]
 extern crate regex;
 
 mod app;
+mod autodetect;
+mod clef;
+mod compiled_template;
 mod config;
+mod continuation;
+mod cri;
+mod decompress;
+mod embedded;
+mod encoding;
+mod explode;
 mod filter;
+mod follow;
+mod input;
+mod json_stream;
+mod level;
 mod log;
 mod log_settings;
+mod logfmt;
+mod output;
+mod pattern;
+mod pipeline;
+mod preset;
 mod process;
+mod stack_trace;
 mod substitution;
+mod syslog;
 mod template;
 mod time;
+mod time_range;
 
-use crate::log_settings::LogSettings;
+use crate::encoding::Encoding;
+use crate::log_settings::{InputFormat, LogSettings};
 use clap_complete::{Shell, generate};
 use config::Config;
+use filter::Filter;
+use follow::FollowReader;
+use input::Source;
+use level::{LevelFilter, Severities};
+use output::Output;
+use regex::Regex;
 use std::fs;
+use std::thread;
 use substitution::Substitution;
+use time_range::{TimeRange, Unparsed};
 
 fn main() {
 	let app = app::app();
@@ -37,8 +67,53 @@ fn main() {
 		Config::get()
 	};
 
+	let input_filenames: Vec<&String> = matches.get_many::<String>("INPUT").unwrap().collect();
+	let follow = matches.get_flag("follow");
+	if follow && input_filenames.len() > 1 {
+		app::app()
+			.error(clap::error::ErrorKind::ArgumentConflict, "--follow can only be used with a single input")
+			.exit();
+	}
+	if matches.get_one::<String>("input-format").map(String::as_str) == Some("json-stream") && input_filenames.len() > 1 {
+		app::app()
+			.error(
+				clap::error::ErrorKind::ArgumentConflict,
+				"--input-format json-stream can only be used with a single input",
+			)
+			.exit();
+	}
+
+	let mut inputs: Vec<(Source, Box<dyn io::Read + Send>)> = if let [input_filename] = input_filenames[..] {
+		vec![(Source::new(input_filename), input_read(input_filename, follow))]
+	} else {
+		input_filenames
+			.iter()
+			.enumerate()
+			.map(|(index, input_filename)| (Source::with_tag(input_filename, index), input_read(input_filename, false)))
+			.collect()
+	};
+
 	let mut log_settings = LogSettings::from_config(&config);
 
+	let preset_name = matches.get_one::<String>("preset").map(String::as_str).or_else(|| {
+		if matches.get_flag("no-autodetect") {
+			return None;
+		}
+		let detected = detect_preset(&mut inputs)?;
+		eprintln!("Detected {detected} logs, use --no-autodetect to turn off detection");
+		Some(detected)
+	});
+	let mut inputs: Vec<(Source, Box<dyn io::BufRead + Send>)> = inputs
+		.into_iter()
+		.map(|(source, input)| -> (Source, Box<dyn io::BufRead + Send>) { (source, Box::new(io::BufReader::new(input))) })
+		.collect();
+	if let Some(name) = preset_name {
+		match preset::resolve(name, &config.presets) {
+			Err(e) => panic!("Invalid preset: {e}"),
+			Ok(preset) => log_settings.apply_preset(preset),
+		}
+	}
+
 	if let Some(values) = matches.get_many::<String>("additional-value") {
 		log_settings.add_additional_values(values.map(ToOwned::to_owned).collect());
 	}
@@ -55,6 +130,10 @@ fn main() {
 		log_settings.add_level_keys(values.map(ToString::to_string).collect());
 	}
 
+	if let Some(values) = matches.get_many::<String>("stack-trace-key") {
+		log_settings.add_stack_trace_keys(values.map(ToString::to_string).collect());
+	}
+
 	if let Some(values) = matches.get_many::<(String, String)>("map-level") {
 		log_settings.add_level_map(values.map(ToOwned::to_owned).collect());
 	}
@@ -70,9 +149,74 @@ fn main() {
 		},
 	}
 
+	if let Some(values) = matches.get_many::<String>("parse-embedded-json") {
+		log_settings.add_embedded_json_keys(values.map(ToString::to_string).collect());
+	}
+
+	if let Some(values) = matches.get_many::<String>("parse-regex") {
+		for value in values {
+			match pattern::resolve(value, &config.parse_patterns) {
+				Err(e) => panic!("Invalid parse regex: {e}"),
+				Ok(regex) => log_settings.add_parse_regex(regex),
+			}
+		}
+	}
+
+	if let Some(prefix_regex) = matches.get_one::<String>("prefix-regex") {
+		match Regex::new(prefix_regex) {
+			Err(e) => panic!("Invalid prefix regex: {e}"),
+			Ok(regex) => log_settings.prefix_regex = Some(regex),
+		}
+	}
+
+	if let Some(continuation_regex) = matches.get_one::<String>("continuation-regex") {
+		match Regex::new(continuation_regex) {
+			Err(e) => panic!("Invalid continuation regex: {e}"),
+			Ok(regex) => log_settings.continuation_regex = Some(regex),
+		}
+		// Continuation lines are printed like stack traces
+		log_settings.add_stack_trace_keys(vec![continuation::KEY.to_string()]);
+	}
+
+	log_settings.idle_input = follow || input_filenames.iter().any(|input_filename| *input_filename == "-");
 	log_settings.dump_all = matches.get_flag("dump-all");
 	log_settings.with_prefix = matches.get_flag("with-prefix");
 	log_settings.print_lua = matches.get_flag("print-lua");
+	log_settings.explode_path = matches.get_one::<String>("explode").cloned();
+	if let Some(input_format) = matches.get_one::<String>("input-format").and_then(|name| InputFormat::from_name(name)) {
+		log_settings.input_format = input_format;
+	}
+	if let Some(encoding) = matches.get_one::<String>("encoding").and_then(|name| Encoding::from_name(name)) {
+		log_settings.encoding = encoding;
+	}
+
+	let severities = || Severities::new(config.level_order.clone(), config.level_aliases.clone());
+	let level_filter = match (matches.get_one::<String>("min-level"), matches.get_many::<String>("levels")) {
+		(Some(min_level), _) => Some(LevelFilter::min_level(severities(), min_level)),
+		(None, Some(levels)) => Some(LevelFilter::levels(severities(), &levels.map(String::as_str).collect::<Vec<_>>())),
+		(None, None) => None,
+	};
+	if let Some(level_filter) = level_filter {
+		match level_filter {
+			Err(e) => panic!("Invalid level: {e}"),
+			Ok(level_filter) => log_settings.level_filter = Some(level_filter),
+		}
+	}
+
+	let now = chrono::Utc::now();
+	let [since, until] = ["since", "until"].map(|name| {
+		matches.get_one::<String>(name).map(|bound| match time_range::parse_bound(bound, now) {
+			Err(e) => panic!("Invalid --{name}: {e}"),
+			Ok(bound) => bound,
+		})
+	});
+	if since.is_some() || until.is_some() {
+		let unparsed = matches
+			.get_one::<String>("unparsed-time")
+			.and_then(|name| Unparsed::from_name(name))
+			.unwrap_or(Unparsed::Keep);
+		log_settings.time_range = Some(TimeRange::new(since, until, unparsed));
+	}
 
 	if let Some(values) = matches.get_many::<String>("excluded-value") {
 		log_settings.dump_all = true; // Dump all is implicitly set by exclusion
@@ -80,10 +224,12 @@ fn main() {
 	}
 
 	let implicit_return = !matches.get_flag("no-implicit-filter-return-statement");
-	let maybe_filter = matches.get_one::<String>("filter");
-
-	let input_filename = matches.get_one::<String>("INPUT").unwrap();
-	let mut input = io::BufReader::new(input_read(input_filename));
+	let maybe_filter = matches
+		.get_one::<String>("filter")
+		.map(|filter_expr| match Filter::new(filter_expr, implicit_return) {
+			Err(e) => panic!("Invalid filter expression: {e}"),
+			Ok(filter) => filter,
+		});
 
 	let main_line_format = matches
 		.get_one::<String>("main-line-format")
@@ -94,14 +240,57 @@ fn main() {
 		.map(|s| s.to_string())
 		.unwrap_or_else(|| config.additional_value_format.to_string());
 
-	let handlebars = template::fblog_handlebar_registry(main_line_format, additional_value_format);
-	process::process_input(&log_settings, &mut input, maybe_filter, implicit_return, &handlebars)
+	let substitution = log_settings.substitution.clone().unwrap_or_default();
+	let templates = template::Templates::new(main_line_format, additional_value_format, substitution);
+	let threads = if log_settings.time_range.as_ref().is_some_and(TimeRange::attaches_unparsed) {
+		1
+	} else {
+		threads(&matches, &input_filenames, follow)
+	};
+	let mut out = Output::stdout();
+	let result = if let [(source, input)] = &mut inputs[..] {
+		process::process_input(&log_settings, source, input.as_mut(), maybe_filter.as_ref(), &templates, threads, &mut out)
+	} else {
+		process::process_merged_inputs(&log_settings, inputs, maybe_filter.as_ref(), &templates, threads, &mut out)
+	};
+
+	if result.and_then(|()| out.flush()).is_err() {
+		// Output end reached, e.g. a closed pipe
+		std::process::exit(14);
+	}
+}
+
+/// Detects the format of the inputs from their first lines. The sampled lines are read again when processing the inputs.
+fn detect_preset(inputs: &mut [(Source, Box<dyn io::Read + Send>)]) -> Option<&'static str> {
+	let mut texts = Vec::with_capacity(inputs.len());
+	for (_, input) in inputs.iter_mut() {
+		let sample = autodetect::read_sample(input);
+		texts.push(String::from_utf8_lossy(&sample).into_owned());
+		let rest = std::mem::replace(input, Box::new(io::empty()));
+		*input = Box::new(io::Cursor::new(sample).chain(rest));
+	}
+	autodetect::detect(&texts)
 }
 
-fn input_read(input_filename: &str) -> Box<dyn io::Read> {
-	if input_filename == "-" {
+/// Uses all cores for files unless `--threads` is given.
+fn threads(matches: &clap::ArgMatches, input_filenames: &[&String], follow: bool) -> usize {
+	match matches.get_one::<u16>("threads") {
+		Some(threads) => usize::from(*threads),
+		None if !follow && input_filenames.iter().all(|input_filename| *input_filename != "-") => {
+			thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1)
+		}
+		None => 1,
+	}
+}
+
+fn input_read(input_filename: &str, follow: bool) -> Box<dyn io::Read + Send> {
+	let input: Box<dyn io::Read + Send> = if input_filename == "-" {
 		Box::new(io::stdin())
+	} else if follow {
+		Box::new(FollowReader::open(input_filename).unwrap_or_else(|_| panic!("Can't open file: {input_filename}")))
 	} else {
 		Box::new(fs::File::open(input_filename).unwrap_or_else(|_| panic!("Can't open file: {input_filename}")))
-	}
+	};
+	let input = decompress::decompress(input).unwrap_or_else(|e| panic!("Can't read input {input_filename}: {e}"));
+	encoding::decode_input(input).unwrap_or_else(|e| panic!("Can't read input {input_filename}: {e}"))
 }
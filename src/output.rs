@@ -0,0 +1,95 @@
+use std::io::{self, IsTerminal, Write};
+
+/// Output is written in blocks of this size when stdout is no terminal.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Buffered output that writes every complete line on a terminal and large blocks otherwise.
+///
+/// Every block is written with a single call, stdout is only locked once per block.
+pub struct Output<W: Write> {
+	inner: W,
+	buffer: Vec<u8>,
+	line_buffered: bool,
+}
+
+impl Output<io::Stdout> {
+	pub fn stdout() -> Output<io::Stdout> {
+		let stdout = io::stdout();
+		let line_buffered = stdout.is_terminal();
+		Output::new(stdout, line_buffered)
+	}
+}
+
+impl<W: Write> Output<W> {
+	pub fn new(inner: W, line_buffered: bool) -> Output<W> {
+		Output {
+			inner,
+			buffer: Vec::with_capacity(BLOCK_SIZE),
+			line_buffered,
+		}
+	}
+
+	fn write_buffer(&mut self) -> io::Result<()> {
+		if !self.buffer.is_empty() {
+			self.inner.write_all(&self.buffer)?;
+			self.buffer.clear();
+		}
+		self.inner.flush()
+	}
+}
+
+impl<W: Write> Write for Output<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.buffer.extend_from_slice(buf);
+		if self.buffer.len() >= BLOCK_SIZE || (self.line_buffered && buf.contains(&b'\n')) {
+			self.write_buffer()?;
+		}
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.write_buffer()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn line_buffered() {
+		let mut output = Output::new(Vec::new(), true);
+		write!(output, "first").unwrap();
+		assert!(output.inner.is_empty());
+		writeln!(output, " line").unwrap();
+		assert_eq!(output.inner, b"first line\n");
+	}
+
+	#[test]
+	fn block_buffered() {
+		let mut output = Output::new(Vec::new(), false);
+		writeln!(output, "first line").unwrap();
+		assert!(output.inner.is_empty());
+		output.write_all(&[b'x'; BLOCK_SIZE]).unwrap();
+		assert_eq!(output.inner.len(), BLOCK_SIZE + 11);
+		writeln!(output, "last line").unwrap();
+		output.flush().unwrap();
+		assert!(output.inner.ends_with(b"xlast line\n"));
+	}
+
+	#[test]
+	fn write_error() {
+		struct BrokenPipe;
+		impl Write for BrokenPipe {
+			fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+				Err(io::ErrorKind::BrokenPipe.into())
+			}
+			fn flush(&mut self) -> io::Result<()> {
+				Ok(())
+			}
+		}
+
+		let mut output = Output::new(BrokenPipe, true);
+		assert_eq!(writeln!(output, "line").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+	}
+}
@@ -0,0 +1,179 @@
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Patterns for common plain text log formats, usable by name with `--parse-regex`.
+pub static BUILTIN_PATTERNS: &[(&str, &str)] = &[
+	(
+		"combined",
+		r#"^(?P<remote_addr>\S+) \S+ (?P<remote_user>\S+) \[(?P<time>[^\]]+)\] "(?P<message>(?P<method>\S+) (?P<path>\S+)(?: (?P<protocol>[^"]*))?)" (?P<status>\d{3}) (?P<bytes>\S+) "(?P<referer>[^"]*)" "(?P<user_agent>[^"]*)""#,
+	),
+	(
+		"common",
+		r#"^(?P<remote_addr>\S+) \S+ (?P<remote_user>\S+) \[(?P<time>[^\]]+)\] "(?P<message>(?P<method>\S+) (?P<path>\S+)(?: (?P<protocol>[^"]*))?)" (?P<status>\d{3}) (?P<bytes>\S+)"#,
+	),
+	(
+		"logback",
+		r"^(?P<time>(?:\d{4}-\d{2}-\d{2}[ T])?\d{2}:\d{2}:\d{2}[.,]\d{3}) \[(?P<thread>[^\]]*)\] (?P<level>[A-Za-z]+)\s+(?P<logger>\S+) - (?P<message>.*)$",
+	),
+	(
+		"log4j",
+		r"^(?P<relative_time>\d+) \[(?P<thread>[^\]]*)\] (?P<level>[A-Za-z]+)\s+(?P<logger>\S+) (?:(?P<ndc>\S.*?) )?- (?P<message>.*)$",
+	),
+];
+
+/// Names of builtin patterns for the same layout. The default `PatternLayout` of Log4j 2
+/// (`%d{HH:mm:ss.SSS} [%t] %-5level %logger{36} - %msg%n`) is the default layout of Logback.
+static PATTERN_ALIASES: &[(&str, &str)] = &[("log4j2", "logback")];
+
+#[derive(Debug)]
+pub enum Error {
+	MissingNamedGroup,
+	RegexParse(regex::Error),
+}
+
+impl From<regex::Error> for Error {
+	fn from(value: regex::Error) -> Self {
+		Self::RegexParse(value)
+	}
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::MissingNamedGroup => f.write_str("The regular expression has no named capture group like `(?P<message>.*)`"),
+			Self::RegexParse(rx) => f.write_fmt(format_args!("Regular expression could not be created: {rx}")),
+		}
+	}
+}
+
+/// Resolves a pattern name from the config file or the builtin patterns. Everything else is used as regular expression.
+pub fn resolve(name_or_regex: &str, config_patterns: &BTreeMap<String, String>) -> Result<Regex, Error> {
+	let pattern = config_patterns
+		.get(name_or_regex)
+		.map(String::as_str)
+		.or_else(|| {
+			let name = PATTERN_ALIASES
+				.iter()
+				.find(|(alias, _)| *alias == name_or_regex)
+				.map_or(name_or_regex, |(_, name)| *name);
+			BUILTIN_PATTERNS.iter().find(|(builtin, _)| *builtin == name).map(|(_, pattern)| *pattern)
+		})
+		.unwrap_or(name_or_regex);
+
+	let regex = Regex::new(pattern)?;
+	if regex.capture_names().flatten().next().is_none() {
+		return Err(Error::MissingNamedGroup);
+	}
+	Ok(regex)
+}
+
+/// Turns the named capture groups of a matching line into the fields of a log entry.
+pub fn parse(regex: &Regex, line: &str) -> Option<Map<String, Value>> {
+	let captures = regex.captures(line)?;
+	Some(
+		regex
+			.capture_names()
+			.flatten()
+			.filter_map(|name| captures.name(name).map(|value| (name.to_string(), Value::String(value.as_str().to_string()))))
+			.collect(),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn parse_with(name_or_regex: &str, line: &str) -> Option<Value> {
+		let regex = resolve(name_or_regex, &BTreeMap::new()).unwrap();
+		parse(&regex, line).map(Value::Object)
+	}
+
+	#[test]
+	fn custom_regex() {
+		assert_eq!(
+			parse_with(r"^(?P<level>\w+): (?P<message>.*)$", "WARN: disk full"),
+			Some(json!({"level": "WARN", "message": "disk full"}))
+		);
+		assert_eq!(parse_with(r"^(?P<level>\w+): (?P<message>.*)$", "no match"), None);
+	}
+
+	#[test]
+	fn config_pattern() {
+		let config_patterns = BTreeMap::from([("mine".to_string(), r"^(?P<message>.*)$".to_string())]);
+		let regex = resolve("mine", &config_patterns).unwrap();
+		assert_eq!(parse(&regex, "hello").map(Value::Object), Some(json!({"message": "hello"})));
+	}
+
+	#[test]
+	fn invalid_patterns() {
+		assert!(matches!(resolve("(unclosed", &BTreeMap::new()), Err(Error::RegexParse(_))));
+		assert!(matches!(resolve(r"^\w+$", &BTreeMap::new()), Err(Error::MissingNamedGroup)));
+	}
+
+	#[test]
+	fn combined_log_format() {
+		assert_eq!(
+			parse_with(
+				"combined",
+				r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326 "http://www.example.com/start.html" "Mozilla/4.08""#
+			),
+			Some(json!({
+				"remote_addr": "127.0.0.1",
+				"remote_user": "frank",
+				"time": "10/Oct/2000:13:55:36 -0700",
+				"message": "GET /apache_pb.gif HTTP/1.0",
+				"method": "GET",
+				"path": "/apache_pb.gif",
+				"protocol": "HTTP/1.0",
+				"status": "200",
+				"bytes": "2326",
+				"referer": "http://www.example.com/start.html",
+				"user_agent": "Mozilla/4.08"
+			}))
+		);
+	}
+
+	#[test]
+	fn logback_layout() {
+		assert_eq!(
+			parse_with("logback", "2024-05-21 11:21:29.536 [main] INFO  com.example.App - Started App in 2.3 seconds"),
+			Some(json!({
+				"time": "2024-05-21 11:21:29.536",
+				"thread": "main",
+				"level": "INFO",
+				"logger": "com.example.App",
+				"message": "Started App in 2.3 seconds"
+			}))
+		);
+	}
+
+	#[test]
+	fn log4j2_layout() {
+		assert_eq!(
+			parse_with("log4j2", "11:21:29.536 [main] ERROR org.example.App - Failed"),
+			Some(json!({
+				"time": "11:21:29.536",
+				"thread": "main",
+				"level": "ERROR",
+				"logger": "org.example.App",
+				"message": "Failed"
+			}))
+		);
+	}
+
+	#[test]
+	fn log4j_layout() {
+		assert_eq!(
+			parse_with("log4j", "176 [main] INFO  org.example.Foo - Entering application."),
+			Some(json!({
+				"relative_time": "176",
+				"thread": "main",
+				"level": "INFO",
+				"logger": "org.example.Foo",
+				"message": "Entering application."
+			}))
+		);
+	}
+}
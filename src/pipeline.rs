@@ -0,0 +1,138 @@
+use crate::continuation::Continuations;
+use crate::filter::{Filter, FilterExpression};
+use crate::input::{InputLine, Source};
+use crate::log_settings::LogSettings;
+use crate::process;
+use crate::template::Templates;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+
+/// Lines a worker thread parses, filters and renders at once.
+const BATCH_SIZE: usize = 512;
+/// Batches waiting for every worker thread, limits the memory used when the output is slow.
+const QUEUE_SIZE: usize = 4;
+
+type Batch = Vec<(usize, io::Result<InputLine>)>;
+
+/// Processes lines together with the index of their source. Continuation lines are joined to their line first,
+/// the lines are then read by a separate thread so a line waiting for continuation lines can't block on the input.
+///
+/// With more than one thread, batches of lines are distributed round-robin to worker threads.
+/// A writer thread collects the rendered batches in the same order, so the output keeps the order of the input.
+pub fn process_lines<I>(
+	log_settings: &LogSettings,
+	sources: &[Source],
+	lines: I,
+	maybe_filter: Option<&Filter>,
+	templates: &Templates,
+	threads: usize,
+	out: &mut (dyn Write + Send),
+) -> io::Result<()>
+where
+	I: Iterator<Item = (usize, io::Result<InputLine>)> + Send,
+{
+	let Some(continuation_regex) = &log_settings.continuation_regex else {
+		return process_joined_lines(log_settings, sources, lines, maybe_filter, templates, threads, out);
+	};
+	thread::scope(|scope| {
+		let (line_sender, line_receiver) = mpsc::sync_channel(BATCH_SIZE);
+		scope.spawn(move || {
+			for line in lines {
+				if line_sender.send(line).is_err() {
+					// Processing stopped
+					return;
+				}
+			}
+		});
+		let lines = Continuations::new(log_settings, continuation_regex, line_receiver);
+		process_joined_lines(log_settings, sources, lines, maybe_filter, templates, threads, out)
+	})
+}
+
+fn process_joined_lines<I>(
+	log_settings: &LogSettings,
+	sources: &[Source],
+	lines: I,
+	maybe_filter: Option<&Filter>,
+	templates: &Templates,
+	threads: usize,
+	out: &mut (dyn Write + Send),
+) -> io::Result<()>
+where
+	I: Iterator<Item = (usize, io::Result<InputLine>)>,
+{
+	if threads <= 1 {
+		for (index, line) in lines {
+			process::process_line(log_settings, &sources[index], line, maybe_filter, templates, out)?;
+		}
+		return Ok(());
+	}
+
+	let maybe_filter_expression = maybe_filter.map(Filter::expression);
+	thread::scope(|scope| {
+		let mut batch_senders = Vec::with_capacity(threads);
+		let mut output_receivers = Vec::with_capacity(threads);
+		for _ in 0..threads {
+			let (batch_sender, batch_receiver) = mpsc::sync_channel::<Batch>(QUEUE_SIZE);
+			let (output_sender, output_receiver) = mpsc::sync_channel::<Vec<u8>>(QUEUE_SIZE);
+			batch_senders.push(batch_sender);
+			output_receivers.push(output_receiver);
+			scope.spawn(move || process_batches(log_settings, sources, maybe_filter_expression, templates, batch_receiver, output_sender));
+		}
+		let writer = scope.spawn(move || write_outputs(output_receivers, out));
+
+		distribute_batches(lines, batch_senders);
+		writer.join().expect("Writer thread should not panic")
+	})
+}
+
+fn distribute_batches<I>(lines: I, batch_senders: Vec<SyncSender<Batch>>)
+where
+	I: Iterator<Item = (usize, io::Result<InputLine>)>,
+{
+	let mut lines = lines.peekable();
+	for batch_sender in batch_senders.iter().cycle() {
+		if lines.peek().is_none() {
+			return;
+		}
+		let batch: Batch = lines.by_ref().take(BATCH_SIZE).collect();
+		if batch_sender.send(batch).is_err() {
+			// Workers stopped
+			return;
+		}
+	}
+}
+
+fn process_batches(
+	log_settings: &LogSettings,
+	sources: &[Source],
+	maybe_filter_expression: Option<&FilterExpression>,
+	templates: &Templates,
+	batch_receiver: Receiver<Batch>,
+	output_sender: SyncSender<Vec<u8>>,
+) {
+	let maybe_filter = maybe_filter_expression.map(|filter_expression| filter_expression.compile().expect("Filter expression was already compiled"));
+	for batch in batch_receiver {
+		let mut output = Vec::new();
+		for (index, line) in batch {
+			process::process_line(log_settings, &sources[index], line, maybe_filter.as_ref(), templates, &mut output).expect("Should be able to write to memory");
+		}
+		if output_sender.send(output).is_err() {
+			return;
+		}
+	}
+}
+
+/// Writes the output of the workers in the order the batches were distributed.
+/// The worker that would have received the batch after the last one ends the output.
+/// A write error stops the workers, as their output can't be sent anymore.
+fn write_outputs(output_receivers: Vec<Receiver<Vec<u8>>>, out: &mut (dyn Write + Send)) -> io::Result<()> {
+	for output_receiver in output_receivers.iter().cycle() {
+		let Ok(output) = output_receiver.recv() else {
+			return Ok(());
+		};
+		out.write_all(&output)?;
+	}
+	Ok(())
+}
@@ -0,0 +1,247 @@
+use crate::time::EpochUnit;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Keys, levels and timestamps of a logging library. Unset fields keep the settings of the config file.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Preset {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub message_keys: Option<Vec<String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub time_keys: Option<Vec<String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub level_keys: Option<Vec<String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub level_map: Option<BTreeMap<String, String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub epoch_unit: Option<EpochUnit>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub excluded_values: Option<Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+	UnknownPreset(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::UnknownPreset(name) => write!(f, "Unknown preset `{name}`, builtin presets are {}", BUILTIN_PRESETS.join(", ")),
+		}
+	}
+}
+
+pub static BUILTIN_PRESETS: &[&str] = &[
+	"bunyan",
+	"pino",
+	"zap",
+	"logrus",
+	"slog",
+	"structlog",
+	"winston",
+	"ecs",
+	"gelf",
+	"clef",
+	"journald",
+	"otlp",
+	"logfmt",
+	"syslog",
+];
+
+/// Resolves a preset from the config file or the builtin presets.
+/// Fields set in the config file replace the fields of a builtin preset with the same name.
+pub fn resolve(name: &str, config_presets: &BTreeMap<String, Preset>) -> Result<Preset, Error> {
+	let builtin = builtin(name);
+	let configured = config_presets.get(name);
+	match (builtin, configured) {
+		(None, None) => Err(Error::UnknownPreset(name.to_string())),
+		(Some(builtin), None) => Ok(builtin),
+		(None, Some(configured)) => Ok(configured.clone()),
+		(Some(builtin), Some(configured)) => {
+			let configured = configured.clone();
+			Ok(Preset {
+				message_keys: configured.message_keys.or(builtin.message_keys),
+				time_keys: configured.time_keys.or(builtin.time_keys),
+				level_keys: configured.level_keys.or(builtin.level_keys),
+				level_map: configured.level_map.or(builtin.level_map),
+				epoch_unit: configured.epoch_unit.or(builtin.epoch_unit),
+				excluded_values: configured.excluded_values.or(builtin.excluded_values),
+			})
+		}
+	}
+}
+
+fn builtin(name: &str) -> Option<Preset> {
+	let preset = match name {
+		// https://github.com/trentm/node-bunyan#levels
+		"bunyan" => preset("msg", "time", numeric_levels(), EpochUnit::Auto, &["pid", "hostname", "v"]),
+		"pino" => preset("msg", "time", numeric_levels(), EpochUnit::Milliseconds, &["pid", "hostname", "v"]),
+		// Production config of zap, `ts` is a decimal number of seconds
+		"zap" => preset("msg", "ts", levels(&[("dpanic", "error"), ("panic", "fatal")]), EpochUnit::Seconds, &[]),
+		"logrus" => preset("msg", "time", levels(&[("warning", "warn"), ("panic", "fatal")]), EpochUnit::Seconds, &[]),
+		"slog" => preset("msg", "time", BTreeMap::new(), EpochUnit::Auto, &[]),
+		"structlog" => preset(
+			"event",
+			"timestamp",
+			levels(&[("warning", "warn"), ("exception", "error"), ("critical", "fatal")]),
+			EpochUnit::Seconds,
+			&[],
+		),
+		"winston" => preset(
+			"message",
+			"timestamp",
+			levels(&[("http", "info"), ("verbose", "debug"), ("silly", "trace")]),
+			EpochUnit::Milliseconds,
+			&[],
+		),
+		// Elastic Common Schema, `log.level` is either a dotted key or nested
+		"ecs" => Preset {
+			message_keys: strings(&["message"]),
+			time_keys: strings(&["@timestamp"]),
+			level_keys: strings(&["log.level", "log > level"]),
+			excluded_values: strings(&["ecs.version", "ecs > version"]),
+			..Default::default()
+		},
+		"gelf" => Preset {
+			message_keys: strings(&["short_message", "full_message"]),
+			time_keys: strings(&["timestamp"]),
+			level_keys: strings(&["level"]),
+			level_map: Some(syslog_levels()),
+			epoch_unit: Some(EpochUnit::Seconds),
+			excluded_values: strings(&["version"]),
+		},
+		// Compact log event format of Serilog
+		"clef" => Preset {
+			message_keys: strings(&["@m", "@mt"]),
+			time_keys: strings(&["@t"]),
+			level_keys: strings(&["@l"]),
+			level_map: Some(levels(&[
+				("Verbose", "trace"),
+				("Debug", "debug"),
+				("Information", "info"),
+				("Warning", "warn"),
+				("Error", "error"),
+				("Fatal", "fatal"),
+			])),
+			excluded_values: strings(&["@i"]),
+			..Default::default()
+		},
+		// Output of `journalctl -o json`
+		"journald" => Preset {
+			message_keys: strings(&["MESSAGE"]),
+			time_keys: strings(&["__REALTIME_TIMESTAMP"]),
+			level_keys: strings(&["PRIORITY"]),
+			level_map: Some(syslog_levels()),
+			epoch_unit: Some(EpochUnit::Microseconds),
+			excluded_values: strings(&["__CURSOR", "__MONOTONIC_TIMESTAMP", "__SEQNUM", "__SEQNUM_ID", "_BOOT_ID", "_MACHINE_ID"]),
+		},
+		// OpenTelemetry log records, severity numbers are 1-24
+		"otlp" => Preset {
+			message_keys: strings(&["body > stringValue", "body"]),
+			time_keys: strings(&["timeUnixNano", "observedTimeUnixNano"]),
+			level_keys: strings(&["severityText", "severityNumber"]),
+			level_map: Some((1..=24).map(|number| (number.to_string(), OTLP_LEVELS[(number - 1) / 4].to_string())).collect()),
+			epoch_unit: Some(EpochUnit::Nanoseconds),
+			excluded_values: strings(&["observedTimeUnixNano", "flags", "droppedAttributesCount"]),
+		},
+		"logfmt" => Preset {
+			time_keys: strings(&["ts"]),
+			..Default::default()
+		},
+		"syslog" => Preset {
+			excluded_values: strings(&["version", "facility", "severity"]),
+			..Default::default()
+		},
+		_ => return None,
+	};
+	Some(preset)
+}
+
+/// Every 4 severity numbers of OpenTelemetry belong to one level.
+const OTLP_LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "fatal"];
+
+fn strings(values: &[&str]) -> Option<Vec<String>> {
+	Some(values.iter().map(ToString::to_string).collect())
+}
+
+fn preset(message_key: &str, time_key: &str, level_map: BTreeMap<String, String>, epoch_unit: EpochUnit, excluded_values: &[&str]) -> Preset {
+	Preset {
+		message_keys: Some(vec![message_key.to_string()]),
+		time_keys: Some(vec![time_key.to_string()]),
+		level_keys: Some(vec!["level".to_string()]),
+		level_map: Some(level_map),
+		epoch_unit: Some(epoch_unit),
+		excluded_values: strings(excluded_values),
+	}
+}
+
+fn levels(levels: &[(&str, &str)]) -> BTreeMap<String, String> {
+	levels.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect()
+}
+
+/// Syslog severities 0 (emergency) to 7 (debug).
+fn syslog_levels() -> BTreeMap<String, String> {
+	levels(&[
+		("0", "fatal"),
+		("1", "fatal"),
+		("2", "fatal"),
+		("3", "error"),
+		("4", "warn"),
+		("5", "info"),
+		("6", "info"),
+		("7", "debug"),
+	])
+}
+
+fn numeric_levels() -> BTreeMap<String, String> {
+	levels(&[
+		("10", "trace"),
+		("20", "debug"),
+		("30", "info"),
+		("40", "warn"),
+		("50", "error"),
+		("60", "fatal"),
+	])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn resolve_builtin_presets() {
+		for name in BUILTIN_PRESETS {
+			assert!(resolve(name, &BTreeMap::new()).is_ok(), "{name}");
+		}
+		let pino = resolve("pino", &BTreeMap::new()).unwrap();
+		assert_eq!(pino.message_keys, Some(vec!["msg".to_string()]));
+		assert_eq!(pino.epoch_unit, Some(EpochUnit::Milliseconds));
+		assert_eq!(pino.level_map.unwrap().get("30").map(String::as_str), Some("info"));
+	}
+
+	#[test]
+	fn override_builtin_preset() {
+		let configured = Preset {
+			message_keys: Some(vec!["message".to_string()]),
+			excluded_values: Some(vec![]),
+			..Default::default()
+		};
+		let pino = resolve("pino", &BTreeMap::from([("pino".to_string(), configured)])).unwrap();
+		assert_eq!(pino.message_keys, Some(vec!["message".to_string()]));
+		assert_eq!(pino.time_keys, Some(vec!["time".to_string()]));
+		assert_eq!(pino.excluded_values, Some(vec![]));
+	}
+
+	#[test]
+	fn unknown_preset() {
+		let custom = Preset {
+			message_keys: Some(vec!["text".to_string()]),
+			..Default::default()
+		};
+		let presets = BTreeMap::from([("custom".to_string(), custom.clone())]);
+		assert_eq!(resolve("custom", &presets), Ok(custom));
+		assert_eq!(resolve("log4js", &presets), Err(Error::UnknownPreset("log4js".to_string())));
+	}
+}
@@ -1,11 +1,24 @@
-use crate::filter;
-use crate::log;
-use crate::log_settings::LogSettings;
-use handlebars::Handlebars;
+use crate::continuation;
+use crate::cri::{self, CriLines};
+use crate::embedded;
+use crate::encoding;
+use crate::explode::{self, LogEntry};
+use crate::filter::{self, Filter};
+use crate::input::{InputLine, MergedLines, Source};
+use crate::json_stream::{JsonStream, JsonStreamItem};
+use crate::log::{self, EntryContext, FlatEntry};
+use crate::log_settings::{InputFormat, LogSettings};
+use crate::logfmt;
+use crate::pattern;
+use crate::pipeline;
+use crate::syslog;
+use crate::template::Templates;
 use lazy_static::lazy_static;
+use regex::{Captures, Regex};
 use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::io;
 use std::io::Write;
-use std::io::{self, BufRead};
 use yansi::{Color, Paint};
 
 lazy_static! {
@@ -14,83 +27,326 @@ lazy_static! {
 
 pub fn process_input(
 	log_settings: &LogSettings,
+	source: &Source,
+	input: &mut (dyn io::BufRead + Send),
+	maybe_filter: Option<&Filter>,
+	templates: &Templates,
+	threads: usize,
+	out: &mut (dyn Write + Send),
+) -> io::Result<()> {
+	match log_settings.input_format {
+		InputFormat::Json | InputFormat::Logfmt | InputFormat::Syslog => {
+			let lines = CriLines::new(encoding::Lines::new(input, log_settings.encoding)).map(|line| (0, line.map(InputLine::new)));
+			pipeline::process_lines(log_settings, std::slice::from_ref(source), lines, maybe_filter, templates, threads, out)
+		}
+		InputFormat::JsonStream => process_json_stream(log_settings, source, input, maybe_filter, templates, out),
+	}
+}
+
+fn process_json_stream(
+	log_settings: &LogSettings,
+	source: &Source,
 	input: &mut dyn io::BufRead,
-	maybe_filter: Option<&String>,
-	implicit_return: bool,
-	handlebars: &Handlebars<'static>,
-) {
-	for line in input.lines() {
-		match line {
-			Ok(read_line) => match process_input_line(log_settings, &read_line, None, maybe_filter, implicit_return, handlebars) {
-				Ok(_) => (),
-				Err(_) => print_raw_line(&read_line, &ORANGE),
+	maybe_filter: Option<&Filter>,
+	templates: &Templates,
+	out: &mut dyn Write,
+) -> io::Result<()> {
+	let context = EntryContext {
+		source: Some(source),
+		..Default::default()
+	};
+	for item in JsonStream::new(input, log_settings.encoding) {
+		match item {
+			Ok(JsonStreamItem::Value(value)) => match explode::into_log_entries(value, log_settings.explode_path.as_deref()) {
+				Ok(log_entries) => {
+					for log_entry in log_entries {
+						match log_entry {
+							Ok(mut log_entry) => {
+								embedded::unwrap_embedded_json(&mut log_entry, &log_settings.embedded_json_keys);
+								process_json_log_entry(log_settings, &context, &log_entry, maybe_filter, templates, out)?;
+							}
+							Err(value) => print_raw_line(out, &value.to_string(), &ORANGE)?,
+						}
+					}
+				}
+				Err(value) => print_raw_line(out, &value.to_string(), &ORANGE)?,
 			},
-			Err(e) => {
-				print_raw_line(&format!("Could not read line: {e}"), &Color::Red);
+			Ok(JsonStreamItem::Invalid(line)) => print_raw_line(out, &line, &ORANGE)?,
+			Err(e) => print_raw_line(out, &format!("Could not read input: {e}"), &Color::Red)?,
+		}
+	}
+	Ok(())
+}
+
+pub fn process_merged_inputs(
+	log_settings: &LogSettings,
+	inputs: Vec<(Source, Box<dyn io::BufRead + Send>)>,
+	maybe_filter: Option<&Filter>,
+	templates: &Templates,
+	threads: usize,
+	out: &mut (dyn Write + Send),
+) -> io::Result<()> {
+	let (sources, readers): (Vec<Source>, Vec<Box<dyn io::BufRead + Send>>) = inputs.into_iter().unzip();
+	let lines = MergedLines::new(log_settings, readers);
+	pipeline::process_lines(log_settings, &sources, lines, maybe_filter, templates, threads, out)
+}
+
+pub fn process_line(
+	log_settings: &LogSettings,
+	source: &Source,
+	line: io::Result<InputLine>,
+	maybe_filter: Option<&Filter>,
+	templates: &Templates,
+	out: &mut dyn Write,
+) -> io::Result<()> {
+	match line {
+		Ok(input_line) => process_input_line(log_settings, source, input_line, maybe_filter, templates, out),
+		Err(e) => print_raw_line(out, &format!("Could not read line: {e}"), &Color::Red),
+	}
+}
+
+fn print_raw_line(out: &mut dyn Write, line: &str, c: &Color) -> io::Result<()> {
+	writeln!(out, "{} {}", "??? >".fg(*c).bold(), line)
+}
+
+/// The log entries found in one line of input. Values of a json array that are no log entries are kept as errors.
+pub struct ParsedLine<'a> {
+	pub prefix: Option<Cow<'a, str>>,
+	pub log_entries: Vec<LogEntry>,
+}
+
+impl ParsedLine<'_> {
+	/// Copies the prefix, so the log entries can be kept without the line.
+	pub fn into_owned(self) -> ParsedLine<'static> {
+		ParsedLine {
+			prefix: self.prefix.map(|prefix| Cow::Owned(prefix.into_owned())),
+			log_entries: self.log_entries,
+		}
+	}
+}
+
+/// Parses a line into log entries. The text matched by the prefix regex is returned as prefix.
+/// With `with_prefix` all text before the first opening curly brace is returned as prefix.
+/// Lines that are no json are parsed with the configured regular expressions or read as syslog or logfmt.
+pub fn parse_log_entries<'a>(log_settings: &LogSettings, read_line: &'a str) -> Option<ParsedLine<'a>> {
+	let mut parsed_line = parse_line(log_settings, read_line)?;
+	for log_entry in parsed_line.log_entries.iter_mut().flatten() {
+		embedded::unwrap_embedded_json(log_entry, &log_settings.embedded_json_keys);
+	}
+	Some(parsed_line)
+}
+
+fn parse_line<'a>(log_settings: &LogSettings, read_line: &'a str) -> Option<ParsedLine<'a>> {
+	if let Some(cri_line) = cri::parse(read_line) {
+		return Some(parse_cri_line(log_settings, cri_line));
+	}
+
+	if let Some(prefix_regex) = &log_settings.prefix_regex
+		&& let Some(captures) = prefix_regex.captures(read_line)
+	{
+		return Some(parse_prefixed_line(log_settings, read_line, prefix_regex, &captures));
+	}
+
+	parse_content(log_settings, read_line)
+}
+
+fn parse_content<'a>(log_settings: &LogSettings, read_line: &'a str) -> Option<ParsedLine<'a>> {
+	match log_settings.input_format {
+		InputFormat::Logfmt => return logfmt::parse(read_line).map(single_entry),
+		InputFormat::Syslog => return syslog::parse(read_line).map(single_entry),
+		InputFormat::Json | InputFormat::JsonStream => (),
+	}
+
+	let explode_path = log_settings.explode_path.as_deref();
+	match serde_json::from_str::<Value>(read_line).map(|value| explode::into_log_entries(value, explode_path)) {
+		Ok(Ok(log_entries)) => Some(ParsedLine { prefix: None, log_entries }),
+		_ => {
+			if let Some(log_entry) = log_settings.parse_regexes.iter().find_map(|regex| pattern::parse(regex, read_line)) {
+				Some(single_entry(log_entry))
+			} else if let Some(log_entry) = syslog::parse(read_line) {
+				Some(single_entry(log_entry))
+			} else if log_settings.with_prefix
+				&& let Some(pos) = read_line.find('{')
+				&& let Ok(Ok(log_entries)) = serde_json::from_str::<Value>(&read_line[pos..]).map(|value| explode::into_log_entries(value, explode_path))
+			{
+				Some(ParsedLine {
+					prefix: Some(Cow::Borrowed(&read_line[..pos])),
+					log_entries,
+				})
+			} else {
+				logfmt::parse_detected(read_line).map(single_entry)
 			}
 		}
 	}
 }
 
-fn print_raw_line(line: &str, c: &Color) {
-	let write_result = writeln!(&mut io::stdout(), "{} {}", "??? >".fg(*c).bold(), line);
-	if write_result.is_err() {
-		// Output end reached
-		std::process::exit(14);
+/// Parses the text after a prefix matched by the prefix regex. The named capture groups of the prefix are added to every
+/// log entry, unless the log entry already has these fields. Text that is no log entry becomes the message.
+fn parse_prefixed_line<'a>(log_settings: &LogSettings, read_line: &'a str, prefix_regex: &Regex, captures: &Captures) -> ParsedLine<'a> {
+	let prefix_end = captures.get(0).map(|prefix| prefix.end()).unwrap_or(0);
+	let (prefix, rest) = read_line.split_at(prefix_end);
+	let mut parsed_line = parse_content(log_settings, rest).unwrap_or_else(|| {
+		let mut log_entry = Map::new();
+		log_entry.insert("message".to_string(), Value::String(rest.to_string()));
+		single_entry(log_entry)
+	});
+	parsed_line.prefix = Some(Cow::Borrowed(prefix));
+	for log_entry in parsed_line.log_entries.iter_mut().flatten() {
+		for name in prefix_regex.capture_names().flatten() {
+			if let Some(value) = captures.name(name) {
+				log_entry.entry(name).or_insert_with(|| Value::String(value.as_str().to_string()));
+			}
+		}
+	}
+	parsed_line
+}
+
+/// Parses the content of a kubernetes container log line. Its timestamp and stream are added to every log entry,
+/// unless the log entry already has these fields. Content that is no log entry becomes the message.
+fn parse_cri_line<'a>(log_settings: &LogSettings, cri_line: cri::CriLine<'a>) -> ParsedLine<'a> {
+	let mut parsed_line = parse_log_entries(log_settings, cri_line.content).unwrap_or_else(|| {
+		let mut log_entry = Map::new();
+		log_entry.insert("message".to_string(), Value::String(cri_line.content.to_string()));
+		single_entry(log_entry)
+	});
+	for log_entry in parsed_line.log_entries.iter_mut().flatten() {
+		log_entry.entry("time").or_insert_with(|| Value::String(cri_line.timestamp.to_string()));
+		log_entry.entry("stream").or_insert_with(|| Value::String(cri_line.stream.to_string()));
+	}
+	parsed_line
+}
+
+fn single_entry<'a>(log_entry: Map<String, Value>) -> ParsedLine<'a> {
+	ParsedLine {
+		prefix: None,
+		log_entries: vec![Ok(log_entry)],
 	}
 }
 
 fn process_input_line(
 	log_settings: &LogSettings,
-	read_line: &str,
-	maybe_prefix: Option<&str>,
-	maybe_filter: Option<&String>,
-	implicit_return: bool,
-	handlebars: &Handlebars<'static>,
-) -> Result<(), ()> {
-	match serde_json::from_str::<Value>(read_line) {
-		Ok(Value::Object(log_entry)) => {
-			process_json_log_entry(log_settings, maybe_prefix, &log_entry, maybe_filter, implicit_return, handlebars);
-			Ok(())
-		}
-		_ => {
-			if log_settings.with_prefix && maybe_prefix.is_none() {
-				match read_line.find('{') {
-					Some(pos) => {
-						let prefix = &read_line[..pos];
-						let rest = &read_line[pos..];
-						process_input_line(log_settings, rest, Some(prefix), maybe_filter, implicit_return, handlebars)
+	source: &Source,
+	input_line: InputLine,
+	maybe_filter: Option<&Filter>,
+	templates: &Templates,
+	out: &mut dyn Write,
+) -> io::Result<()> {
+	let read_line = &input_line.text;
+	// Continuation lines were joined to the line with newlines
+	let (line, continuation) = match read_line.split_once('\n') {
+		Some((line, continuation)) => (line, Some(continuation)),
+		None => (read_line.as_str(), None),
+	};
+	let parsed_line = match input_line.parsed {
+		Some(parsed_line) => parsed_line,
+		None => parse_log_entries(log_settings, line),
+	};
+	match parsed_line {
+		Some(parsed_line) => {
+			let context = EntryContext {
+				prefix: parsed_line.prefix.as_deref(),
+				source: Some(source),
+			};
+			for log_entry in parsed_line.log_entries {
+				match log_entry {
+					Ok(mut log_entry) => {
+						if let Some(continuation) = continuation {
+							log_entry.insert(continuation::KEY.to_string(), Value::String(continuation.to_string()));
+						}
+						process_json_log_entry(log_settings, &context, &log_entry, maybe_filter, templates, out)?;
 					}
-					None => Err(()),
+					Err(value) => print_raw_line(out, &value.to_string(), &ORANGE)?,
 				}
-			} else {
-				Err(())
 			}
+			Ok(())
 		}
+		None => read_line.split('\n').try_for_each(|line| print_raw_line(out, line, &ORANGE)),
 	}
 }
 
 fn process_json_log_entry(
 	log_settings: &LogSettings,
-	maybe_prefix: Option<&str>,
+	context: &EntryContext,
 	log_entry: &Map<String, Value>,
-	maybe_filter: Option<&String>,
-	implicit_return: bool,
-	handlebars: &Handlebars<'static>,
-) {
+	maybe_filter: Option<&Filter>,
+	templates: &Templates,
+	out: &mut dyn Write,
+) -> io::Result<()> {
+	let flat_entry = FlatEntry::new(log_entry);
+	if let Some(time_range) = &log_settings.time_range
+		&& !time_range.show(log::get_parsed_timestamp(&flat_entry, log_settings))
+	{
+		return Ok(());
+	}
+
+	if let Some(level_filter) = &log_settings.level_filter
+		&& !level_filter.show(log::resolve_level(&flat_entry, log_settings).as_deref())
+	{
+		return Ok(());
+	}
+
 	if let Some(filter) = maybe_filter {
-		match filter::show_log_entry(log_entry, filter, implicit_return, log_settings) {
-			Ok(true) => process_log_entry(log_settings, maybe_prefix, log_entry, handlebars),
-			Ok(false) => (),
+		if log_settings.print_lua {
+			writeln!(out, "{}", filter::lua_record(log_entry))?;
+		}
+		match filter.show_log_entry(log_entry, context.prefix) {
+			Ok(true) => process_log_entry(log_settings, context, &flat_entry, templates, out),
+			Ok(false) => Ok(()),
 			Err(e) => {
 				writeln!(io::stderr(), "{}: '{:?}'", "Failed to apply filter expression".red(), e).expect("Should be able to write to stderr");
+				Ok(())
 			}
 		}
 	} else {
-		process_log_entry(log_settings, maybe_prefix, log_entry, handlebars)
+		process_log_entry(log_settings, context, &flat_entry, templates, out)
 	}
 }
 
-fn process_log_entry(log_settings: &LogSettings, maybe_prefix: Option<&str>, log_entry: &Map<String, Value>, handlebars: &Handlebars<'static>) {
-	log::print_log_line(&mut io::stdout(), maybe_prefix, log_entry, log_settings, handlebars)
+fn process_log_entry(log_settings: &LogSettings, context: &EntryContext, flat_entry: &FlatEntry, templates: &Templates, out: &mut dyn Write) -> io::Result<()> {
+	log::print_log_line(out, context, flat_entry, log_settings, templates)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::substitution::Substitution;
+	use crate::template;
+
+	fn process(log_settings: &LogSettings, line: &str) -> String {
+		let templates = Templates::new(
+			template::DEFAULT_MAIN_LINE_FORMAT.to_string(),
+			template::DEFAULT_ADDITIONAL_VALUE_FORMAT.to_string(),
+			Substitution::default(),
+		);
+		let mut out: Vec<u8> = Vec::new();
+		process_line(
+			log_settings,
+			&Source::new("-"),
+			Ok(InputLine::new(line.to_string())),
+			None,
+			&templates,
+			&mut out,
+		)
+		.unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	#[test]
+	fn raw_lines() {
+		let log_settings = LogSettings::new_default_settings();
+		assert_eq!(process(&log_settings, ""), format!("{} \n", "??? >".fg(*ORANGE).bold()));
+		assert_eq!(
+			process(&log_settings, "not json\n\tat continued"),
+			format!("{} not json\n{} \tat continued\n", "??? >".fg(*ORANGE).bold(), "??? >".fg(*ORANGE).bold())
+		);
+	}
+
+	#[test]
+	fn array_with_other_values() {
+		let log_settings = LogSettings::new_default_settings();
+		let output = process(&log_settings, r#"[{"message": "a"}, 1]"#);
+		assert_eq!(output.lines().count(), 2);
+		assert!(output.lines().next().unwrap().ends_with(": a"));
+		assert!(output.ends_with(&format!("{} 1\n", "??? >".fg(*ORANGE).bold())));
+	}
 }
@@ -0,0 +1,173 @@
+use std::io::{self, Write};
+use yansi::Paint;
+
+/// Indentation of stack traces below the main line.
+const INDENT: &str = "    ";
+
+/// Writes a stack trace of Java, .NET, Node or Python indented below the main line. Runs of frames of the collapsed
+/// packages are replaced by `... N frames omitted`, exception lines and `Caused by:` chains are highlighted.
+pub fn write_stack_trace(out: &mut dyn Write, stack_trace: &str, collapsed_packages: &[String]) -> io::Result<()> {
+	let mut lines = stack_trace.lines().filter(|line| !line.trim().is_empty()).peekable();
+	let mut omitted = Omitted::default();
+	let mut python_frame_indent = None;
+	while let Some(line) = lines.next() {
+		let indent = leading_whitespace(line);
+		let content = line.trim();
+		match frame_location(content) {
+			Some(location) if is_collapsed(location, collapsed_packages) => {
+				if omitted.count == 0 {
+					omitted.indent = indent.clone();
+				}
+				omitted.count += 1;
+				// The source line below a Python frame belongs to the frame
+				if content.starts_with("File \"")
+					&& let Some(source) = lines.peek()
+					&& leading_whitespace(source).len() > indent.len()
+					&& frame_location(source.trim()).is_none()
+				{
+					lines.next();
+				}
+				python_frame_indent = None;
+			}
+			Some(_) => {
+				omitted.write(out)?;
+				let (at, location) = content.split_at(content.find(' ').unwrap_or(0));
+				writeln!(out, "{INDENT}{indent}{}{location}", at.dim())?;
+				python_frame_indent = content.starts_with("File \"").then_some(indent.len());
+			}
+			None => {
+				omitted.write(out)?;
+				if python_frame_indent.is_some_and(|frame_indent| indent.len() > frame_indent) {
+					writeln!(out, "{INDENT}{indent}{content}")?;
+				} else if let Some(cause) = content.strip_prefix("Caused by:") {
+					writeln!(out, "{INDENT}{indent}{}{}", "Caused by:".yellow().bold(), cause.red())?;
+				} else if is_chain_note(content) {
+					writeln!(out, "{INDENT}{indent}{}", content.yellow().bold())?;
+				} else if content.starts_with("... ") || content.starts_with("Traceback ") {
+					writeln!(out, "{INDENT}{indent}{}", content.dim())?;
+				} else {
+					writeln!(out, "{INDENT}{indent}{}", content.red())?;
+				}
+				python_frame_indent = None;
+			}
+		}
+	}
+	omitted.write(out)
+}
+
+/// A run of collapsed frames.
+#[derive(Default)]
+struct Omitted {
+	count: usize,
+	indent: String,
+}
+
+impl Omitted {
+	fn write(&mut self, out: &mut dyn Write) -> io::Result<()> {
+		match self.count {
+			0 => return Ok(()),
+			1 => writeln!(out, "{INDENT}{}{}", self.indent, "... 1 frame omitted".dim())?,
+			count => writeln!(out, "{INDENT}{}{}", self.indent, format!("... {count} frames omitted").dim())?,
+		}
+		self.count = 0;
+		Ok(())
+	}
+}
+
+/// Tabs are replaced by 4 spaces, so Java traces are indented like the others.
+fn leading_whitespace(line: &str) -> String {
+	line[..line.len() - line.trim_start().len()].replace('\t', "    ")
+}
+
+/// The method of frames like `at com.acme.Service.save(Service.java:42)` or the file of `File "app.py", line 3, in run`.
+fn frame_location(content: &str) -> Option<&str> {
+	if let Some(location) = content.strip_prefix("at ") {
+		Some(location)
+	} else {
+		content.strip_prefix("File \"")
+	}
+}
+
+/// Frames are collapsed if their method or the file in parentheses starts with a package, like Node's
+/// `at Module._compile (node:internal/modules/cjs/loader:1105:14)`.
+fn is_collapsed(location: &str, collapsed_packages: &[String]) -> bool {
+	let file = location.rsplit_once(" (").map(|(_, file)| file);
+	collapsed_packages
+		.iter()
+		.any(|package| location.starts_with(package.as_str()) || file.is_some_and(|file| file.starts_with(package.as_str())))
+}
+
+/// Python links chained exceptions with these notes.
+fn is_chain_note(content: &str) -> bool {
+	content.starts_with("During handling of the above exception") || content.starts_with("The above exception was the direct cause")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use regex::Regex;
+
+	fn render(stack_trace: &str, collapsed_packages: &[&str]) -> String {
+		let collapsed_packages: Vec<String> = collapsed_packages.iter().map(ToString::to_string).collect();
+		let mut out: Vec<u8> = Vec::new();
+		write_stack_trace(&mut out, stack_trace, &collapsed_packages).unwrap();
+		let regex = Regex::new("\u{001B}\\[[\\d;]*[^\\d;]").expect("Regex should be valid");
+		regex.replace_all(&String::from_utf8_lossy(&out), "").into_owned()
+	}
+
+	#[test]
+	fn collapse_java_frames() {
+		let stack_trace = "\
+java.lang.IllegalStateException: save failed
+\tat com.acme.Service.save(Service.java:42)
+\tat sun.reflect.NativeMethodAccessorImpl.invoke0(Native Method)
+\tat java.lang.reflect.Method.invoke(Method.java:498)
+\tat org.springframework.aop.Invoker.invoke(Invoker.java:12)
+\tat com.acme.Controller.post(Controller.java:7)
+Caused by: java.io.IOException: disk full
+\tat java.io.FileOutputStream.write(FileOutputStream.java:326)
+\t... 4 more
+";
+		assert_eq!(
+			render(stack_trace, &["java.", "sun.", "org.springframework."]),
+			"    java.lang.IllegalStateException: save failed
+        at com.acme.Service.save(Service.java:42)
+        ... 3 frames omitted
+        at com.acme.Controller.post(Controller.java:7)
+    Caused by: java.io.IOException: disk full
+        ... 1 frame omitted
+        ... 4 more
+"
+		);
+	}
+
+	#[test]
+	fn collapse_node_frames() {
+		let stack_trace = "Error: boom\n    at run (/app/index.js:3:9)\n    at Module._compile (node:internal/modules/cjs/loader:1105:14)";
+		assert_eq!(
+			render(stack_trace, &["node:internal"]),
+			"    Error: boom\n        at run (/app/index.js:3:9)\n        ... 1 frame omitted\n"
+		);
+	}
+
+	#[test]
+	fn collapse_python_frames() {
+		let stack_trace = "\
+Traceback (most recent call last):
+  File \"/app/main.py\", line 3, in <module>
+    run()
+  File \"/usr/lib/python3.12/json/__init__.py\", line 346, in loads
+    return _default_decoder.decode(s)
+json.decoder.JSONDecodeError: Expecting value: line 1 column 1 (char 0)
+";
+		assert_eq!(
+			render(stack_trace, &["/usr/lib/python3"]),
+			"    Traceback (most recent call last):
+      File \"/app/main.py\", line 3, in <module>
+        run()
+      ... 1 frame omitted
+    json.decoder.JSONDecodeError: Expecting value: line 1 column 1 (char 0)
+"
+		);
+	}
+}
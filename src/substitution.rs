@@ -1,7 +1,8 @@
+use std::borrow::Cow;
 use std::fmt;
 
 use regex::{Captures, Regex};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use yansi::Paint;
 
 #[derive(Debug)]
@@ -25,29 +26,43 @@ impl std::fmt::Display for Error {
 	}
 }
 
+#[derive(Clone)]
 pub struct Substitution {
 	pub context_key: String,
 	placeholder_prefix: String,
 	placeholder_suffix: String,
 	placeholder_regex: Regex,
+	printf: bool,
 }
 
 impl Substitution {
 	pub const DEFAULT_PLACEHOLDER_FORMAT: &'static str = "{key}";
+	pub const PRINTF_PLACEHOLDER_FORMAT: &'static str = "%s";
 	pub const KEY_DELIMITER: &'static str = "key";
 	pub const DEFAULT_CONTEXT_KEY: &'static str = "context";
 
 	pub fn new<S: Into<String>>(context_key: Option<S>, placeholder_format: Option<S>) -> Result<Self, Error> {
 		let format = placeholder_format.map_or(Self::DEFAULT_PLACEHOLDER_FORMAT.to_owned(), Into::into);
+		let context_key = context_key.map_or(Self::DEFAULT_CONTEXT_KEY.to_owned(), Into::into);
+		if format == Self::PRINTF_PLACEHOLDER_FORMAT {
+			return Ok(Self {
+				context_key,
+				placeholder_prefix: String::new(),
+				placeholder_suffix: String::new(),
+				placeholder_regex: Regex::new(r"%([-0]*)(\d*)(?:\.(\d+))?([sdifxX%])")?,
+				printf: true,
+			});
+		}
 		let (prefix, suffix) = Self::parse_placeholder_format(&format)?;
 
 		let placeholder_regex = Self::create_regex(prefix, suffix)?;
 
 		Ok(Self {
-			context_key: context_key.map_or(Self::DEFAULT_CONTEXT_KEY.to_owned(), Into::into),
+			context_key,
 			placeholder_prefix: prefix.to_owned(),
 			placeholder_suffix: suffix.to_owned(),
 			placeholder_regex,
+			printf: false,
 		})
 	}
 
@@ -55,36 +70,88 @@ impl Substitution {
 		format.split_once(Self::KEY_DELIMITER).ok_or(Error::MissingIdentifier)
 	}
 
+	/// Matches a path like `user.id` or `items[0]` with an optional format spec, an empty path takes the next argument.
 	fn create_regex(prefix: &str, suffix: &str) -> Result<Regex, regex::Error> {
-		Regex::new(&format!("{}([a-z|A-Z|0-9|_|-]+){}", regex::escape(prefix), regex::escape(suffix)))
+		Regex::new(&format!(r"{}([\w.\[\]-]*)(?::(.*?))?{}", regex::escape(prefix), regex::escape(suffix)))
 	}
 
+	/// Substitutes the placeholders of the message with values of the context, or with top-level fields of the log entry
+	/// if the context does not have them. Returns `None` if the message has no placeholders.
+	///
+	/// Placeholders without a value are highlighted, unless the log entry has no context. Then they are left unchanged,
+	/// as the braces are probably part of the message.
 	pub(crate) fn apply(&self, message: &str, log_entry: &serde_json::Map<String, serde_json::Value>) -> Option<String> {
-		let context_value = log_entry.get(&self.context_key)?;
-
-		Some(
-			self
-				.placeholder_regex
-				.replace_all(message, |caps: &Captures| {
-					let key = &caps[1];
-					let value = match context_value {
-						Value::Object(o) => o.get(key),
-						Value::Array(a) => key.parse().map(|i| a.get::<usize>(i)).unwrap_or(None),
-						_ => None,
-					};
-					match value {
-						None => {
-							format!("{}{}{}", self.placeholder_prefix.dim(), key.red().bold(), self.placeholder_suffix.dim())
-						}
-						Some(value) => {
-							let mut buf = String::new();
-							let _ = self.color_format(&mut buf, value);
-							buf
-						}
-					}
-				})
-				.into_owned(),
-		)
+		let context_value = log_entry.get(&self.context_key);
+		if self.printf && context_value.is_none() {
+			return None;
+		}
+		let mut arguments = match context_value {
+			Some(Value::Array(a)) => a.iter(),
+			_ => [].iter(),
+		};
+
+		let substituted = self.placeholder_regex.replace_all(message, |caps: &Captures| {
+			if self.printf {
+				if &caps[4] == "%" {
+					return "%".to_string();
+				}
+				let spec = Spec::from_printf(&caps[1], &caps[2], caps.get(3).map(|precision| precision.as_str()), &caps[4]);
+				return match (arguments.next(), spec) {
+					(Some(value), Some(spec)) => self.format_value(value, &spec),
+					_ => caps[0].red().bold().to_string(),
+				};
+			}
+			let key = &caps[1];
+			let Some(spec) = caps.get(2).map_or(Some(Spec::default()), |spec| Spec::parse(spec.as_str())) else {
+				// Probably not a placeholder, like `{a: 1}`
+				return caps[0].to_string();
+			};
+			let value = if key.is_empty() {
+				arguments.next()
+			} else {
+				context_value.and_then(|context| lookup(context, key)).or_else(|| lookup_entry(log_entry, key))
+			};
+			match value {
+				None if context_value.is_none() => caps[0].to_string(),
+				None => {
+					let placeholder = &caps[0][self.placeholder_prefix.len()..caps[0].len() - self.placeholder_suffix.len()];
+					format!("{}{}{}", self.placeholder_prefix.dim(), placeholder.red().bold(), self.placeholder_suffix.dim())
+				}
+				Some(value) => self.format_value(value, &spec),
+			}
+		});
+		match substituted {
+			Cow::Borrowed(_) => None,
+			Cow::Owned(substituted) => Some(substituted),
+		}
+	}
+
+	fn format_value(&self, value: &Value, spec: &Spec) -> String {
+		let Some(text) = spec.text(value) else {
+			let mut buf = String::new();
+			let _ = self.color_format(&mut buf, value);
+			return buf;
+		};
+		let numeric = matches!(value, Value::Number(_));
+		let padding = spec.width.saturating_sub(text.chars().count());
+		if spec.zero && numeric {
+			let (sign, digits) = text.strip_prefix('-').map_or(("", text.as_str()), |digits| ("-", digits));
+			return format!("{sign}{}{digits}", "0".repeat(padding)).cyan().bold().to_string();
+		}
+		let (left, right) = match spec.align.unwrap_or(if numeric { Align::Right } else { Align::Left }) {
+			Align::Left => (0, padding),
+			Align::Right => (padding, 0),
+			Align::Center => (padding / 2, padding - padding / 2),
+		};
+		let text = match value {
+			Value::String(_) if !numeric => text.yellow().bold().to_string(),
+			Value::Bool(true) => text.green().bold().to_string(),
+			Value::Bool(false) => text.red().bold().to_string(),
+			Value::Null => text.bold().to_string(),
+			_ => text.cyan().bold().to_string(),
+		};
+		let fill = spec.fill.to_string();
+		format!("{}{text}{}", fill.repeat(left), fill.repeat(right))
 	}
 
 	fn color_format<W: std::fmt::Write>(&self, buf: &mut W, value: &Value) -> Result<(), fmt::Error> {
@@ -127,6 +194,185 @@ impl Substitution {
 	}
 }
 
+#[derive(Clone, Copy)]
+enum Align {
+	Left,
+	Right,
+	Center,
+}
+
+impl Align {
+	fn from_char(c: char) -> Option<Align> {
+		match c {
+			'<' => Some(Align::Left),
+			'>' => Some(Align::Right),
+			'^' => Some(Align::Center),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+	Display,
+	Integer,
+	Float,
+	LowerHex,
+	UpperHex,
+	HumanBytes,
+}
+
+/// A format spec like `.2`, `>5`, `08.3f` or `hbytes`: `[[fill]align][0][width][.precision][type]`.
+struct Spec {
+	fill: char,
+	align: Option<Align>,
+	zero: bool,
+	width: usize,
+	precision: Option<usize>,
+	kind: Kind,
+}
+
+impl Default for Spec {
+	fn default() -> Self {
+		Spec {
+			fill: ' ',
+			align: None,
+			zero: false,
+			width: 0,
+			precision: None,
+			kind: Kind::Display,
+		}
+	}
+}
+
+impl Spec {
+	fn parse(spec: &str) -> Option<Spec> {
+		let mut parsed = Spec::default();
+		let mut chars = spec.chars();
+		let mut rest = match (chars.next(), chars.next().and_then(Align::from_char)) {
+			(Some(fill), Some(align)) => {
+				parsed.fill = fill;
+				parsed.align = Some(align);
+				&spec[fill.len_utf8() + 1..]
+			}
+			(Some(align), _) if Align::from_char(align).is_some() => {
+				parsed.align = Align::from_char(align);
+				&spec[1..]
+			}
+			_ => spec,
+		};
+		if let Some(width) = rest.strip_prefix('0') {
+			parsed.zero = true;
+			rest = width;
+		}
+		let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+		parsed.width = rest[..digits].parse().unwrap_or(0);
+		rest = &rest[digits..];
+		if let Some(precision) = rest.strip_prefix('.') {
+			let digits = precision.find(|c: char| !c.is_ascii_digit()).unwrap_or(precision.len());
+			parsed.precision = Some(precision[..digits].parse().ok()?);
+			rest = &precision[digits..];
+		}
+		parsed.kind = match rest {
+			"" => Kind::Display,
+			"d" => Kind::Integer,
+			"f" => Kind::Float,
+			"x" => Kind::LowerHex,
+			"X" => Kind::UpperHex,
+			"hbytes" => Kind::HumanBytes,
+			_ => return None,
+		};
+		Some(parsed)
+	}
+
+	/// A printf conversion like `%s`, `%-5d` or `%08.3f`.
+	fn from_printf(flags: &str, width: &str, precision: Option<&str>, conversion: &str) -> Option<Spec> {
+		Some(Spec {
+			align: flags.contains('-').then_some(Align::Left),
+			zero: flags.contains('0') && !flags.contains('-'),
+			width: width.parse().unwrap_or(0),
+			precision: precision.map(str::parse).transpose().ok()?,
+			kind: match conversion {
+				"d" | "i" => Kind::Integer,
+				"f" => Kind::Float,
+				"x" => Kind::LowerHex,
+				"X" => Kind::UpperHex,
+				_ => Kind::Display,
+			},
+			..Spec::default()
+		})
+	}
+
+	/// The unstyled text of a value, `None` for arrays and objects.
+	fn text(&self, value: &Value) -> Option<String> {
+		let text = match (value, self.kind) {
+			(Value::Array(_) | Value::Object(_), _) => return None,
+			(Value::Number(n), Kind::Integer) => n
+				.as_i64()
+				.map_or_else(|| format!("{:.0}", n.as_f64().unwrap_or_default().trunc()), |n| n.to_string()),
+			(Value::Number(n), Kind::Float) => format!("{:.*}", self.precision.unwrap_or(6), n.as_f64().unwrap_or_default()),
+			(Value::Number(n), Kind::LowerHex) if let Some(n) = n.as_u64() => format!("{n:x}"),
+			(Value::Number(n), Kind::UpperHex) if let Some(n) = n.as_u64() => format!("{n:X}"),
+			(Value::Number(n), Kind::HumanBytes) => human_bytes(n.as_f64().unwrap_or_default(), self.precision.unwrap_or(1)),
+			(Value::Number(n), _) => match self.precision {
+				Some(precision) => format!("{:.*}", precision, n.as_f64().unwrap_or_default()),
+				None => n.to_string(),
+			},
+			(Value::String(s), _) => match self.precision {
+				Some(precision) => s.chars().take(precision).collect(),
+				None => s.clone(),
+			},
+			(value, _) => value.to_string(),
+		};
+		Some(text)
+	}
+}
+
+/// Formats a number of bytes with binary units like `1.5 KiB`.
+fn human_bytes(bytes: f64, precision: usize) -> String {
+	const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+	let mut value = bytes;
+	let mut unit = 0;
+	while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+		value /= 1024.0;
+		unit += 1;
+	}
+	if unit == 0 {
+		format!("{bytes} B")
+	} else {
+		format!("{value:.precision$} {}", UNITS[unit])
+	}
+}
+
+/// Looks up a path like `user.id`, `items[0]` or `0` in an object or array.
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+	match value {
+		Value::Object(object) => lookup_entry(object, path),
+		_ => path_segments(path).try_fold(value, get),
+	}
+}
+
+/// Keys containing dots like `log.level` are matched before nested paths.
+fn lookup_entry<'a>(log_entry: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+	log_entry.get(path).or_else(|| {
+		let mut segments = path_segments(path);
+		let first = log_entry.get(segments.next()?)?;
+		segments.try_fold(first, get)
+	})
+}
+
+fn path_segments(path: &str) -> impl Iterator<Item = &str> {
+	path.split(['.', '[']).map(|segment| segment.strip_suffix(']').unwrap_or(segment))
+}
+
+fn get<'a>(value: &'a Value, segment: &str) -> Option<&'a Value> {
+	match value {
+		Value::Object(object) => object.get(segment),
+		Value::Array(array) => array.get(segment.parse::<usize>().ok()?),
+		_ => None,
+	}
+}
+
 impl Default for Substitution {
 	fn default() -> Self {
 		Self::new::<String>(None, None).expect("default placeholder should parse")
@@ -192,4 +438,61 @@ mod tests {
 		let result = subst.apply(msg, &entry_context(&subst, context)).unwrap_or(msg.to_owned());
 		assert_eq!("text: better than sleeping, number: 9, bool: true, ignored: {3}", without_style(&result));
 	}
+
+	#[test]
+	fn nested_paths_and_top_level_fallback() {
+		let subst = Substitution::default();
+		let msg = "{user.id} {items[1]} {log.level} {matrix[1][0]} {top} {missing.key}";
+		let mut log_entry = entry_context(&subst, serde_json::json!({"user": {"id": 7}, "items": ["a", "b"], "log.level": "info"}));
+		log_entry.insert("matrix".into(), serde_json::json!([[1], [2]]));
+		log_entry.insert("top".into(), "level".into());
+
+		let result = subst.apply(msg, &log_entry).unwrap();
+		assert_eq!("7 b info 2 level {missing.key}", without_style(&result));
+
+		let result = subst
+			.apply("{top}", &serde_json::Map::from_iter([("top".to_string(), "no context".into())]))
+			.unwrap();
+		assert_eq!("no context", without_style(&result));
+	}
+
+	#[test]
+	fn format_specs() {
+		let subst = Substitution::default();
+		let msg = "{d:.2}|{n:>5}|{s:<5}|{s:*^7}|{n:05}|{m:08.3}|{s:.2}|{b:hbytes}|{k:hbytes}|{n:x}|{n:X}|{d:d}|{a: 1}|{s:bad}";
+		let context = serde_json::json!({"d": 12.3456, "n": 42, "m": -1.5, "s": "ann", "b": 1572864, "k": 512});
+
+		let result = subst.apply(msg, &entry_context(&subst, context)).unwrap();
+		assert_eq!(
+			"12.35|   42|ann  |**ann**|00042|-001.500|an|1.5 MiB|512 B|2a|2A|12|{a: 1}|{s:bad}",
+			without_style(&result)
+		);
+	}
+
+	#[test]
+	fn positional_arguments() {
+		let subst = Substitution::default();
+		let context: Vec<Value> = vec![3.into(), 10.into()];
+		let result = subst.apply("{} of {}, {}", &entry_context(&subst, context)).unwrap();
+		assert_eq!("3 of 10, {}", without_style(&result));
+
+		let subst = Substitution::new(Some("args"), Some(Substitution::PRINTF_PLACEHOLDER_FORMAT)).unwrap();
+		let context = serde_json::json!([1.23456, "bob", 42, 255, 7, 2.5]);
+		let result = subst.apply("%.2f %s %05d %% %x %-3i| %f %s", &entry_context(&subst, context)).unwrap();
+		assert_eq!("1.23 bob 00042 % ff 7  | 2.500000 %s", without_style(&result));
+		assert_eq!(subst.apply("no placeholders", &entry_context(&subst, serde_json::json!([]))), None);
+	}
+
+	#[test]
+	fn no_context() {
+		let subst = Substitution::default();
+		let log_entry = serde_json::Map::from_iter([("user".to_string(), "ann".into())]);
+		let msg = "Parsed {} and {word} for {user}";
+		let result = subst.apply(msg, &log_entry).unwrap_or(msg.to_owned());
+		assert!(result.starts_with("Parsed {} and {word} for "), "Unmatched placeholders should not be styled");
+		assert_eq!("Parsed {} and {word} for ann", without_style(&result));
+
+		let subst = Substitution::new(None, Some(Substitution::PRINTF_PLACEHOLDER_FORMAT)).unwrap();
+		assert_eq!(subst.apply("100%% done in %s", &log_entry), None);
+	}
 }
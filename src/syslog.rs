@@ -0,0 +1,308 @@
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+lazy_static! {
+	static ref RFC5424: Regex = Regex::new(r"^<(\d{1,3})>(\d{1,2}) (\S+) (\S+) (\S+) (\S+) (\S+) ?(.*)$").unwrap();
+	static ref RFC3164: Regex = Regex::new(r"^<(\d{1,3})>([A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}) (?:(\S+) )?([^:\[\s]+)(?:\[([^\]]*)\])?: ?(.*)$").unwrap();
+	static ref RFC3164_WITHOUT_TAG: Regex = Regex::new(r"^<(\d{1,3})>([A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}) (.*)$").unwrap();
+}
+
+static FACILITIES: &[&str] = &[
+	"kern",
+	"user",
+	"mail",
+	"daemon",
+	"auth",
+	"syslog",
+	"lpr",
+	"news",
+	"uucp",
+	"cron",
+	"authpriv",
+	"ftp",
+	"ntp",
+	"security",
+	"console",
+	"solaris-cron",
+	"local0",
+	"local1",
+	"local2",
+	"local3",
+	"local4",
+	"local5",
+	"local6",
+	"local7",
+];
+
+/// Severity keyword and the matching fblog level for every syslog severity.
+static SEVERITIES: &[(&str, &str)] = &[
+	("emerg", "fatal"),
+	("alert", "fatal"),
+	("crit", "fatal"),
+	("err", "error"),
+	("warning", "warn"),
+	("notice", "info"),
+	("info", "info"),
+	("debug", "debug"),
+];
+
+/// Parses a syslog line (RFC 5424 or RFC 3164) into a log entry.
+///
+/// Structured data elements of RFC 5424 are nested objects below `sd`.
+/// A json object as message is merged into the log entry.
+pub fn parse(line: &str) -> Option<Map<String, Value>> {
+	if !line.starts_with('<') {
+		return None;
+	}
+	parse_rfc5424(line).or_else(|| parse_rfc3164(line))
+}
+
+fn parse_rfc5424(line: &str) -> Option<Map<String, Value>> {
+	let captures = RFC5424.captures(line)?;
+	let mut log_entry = parse_priority(&captures[1])?;
+	log_entry.insert("version".to_string(), Value::String(captures[2].to_string()));
+	for (index, key) in [(3, "timestamp"), (4, "hostname"), (5, "appname"), (6, "procid"), (7, "msgid")] {
+		insert_unless_nil(&mut log_entry, key, &captures[index]);
+	}
+
+	let (structured_data, message) = parse_structured_data(&captures[8])?;
+	if !structured_data.is_empty() {
+		log_entry.insert("sd".to_string(), Value::Object(structured_data));
+	}
+	insert_message(&mut log_entry, message.trim_start_matches('\u{feff}'));
+	Some(log_entry)
+}
+
+fn parse_rfc3164(line: &str) -> Option<Map<String, Value>> {
+	if let Some(captures) = RFC3164.captures(line) {
+		let mut log_entry = parse_priority(&captures[1])?;
+		log_entry.insert("timestamp".to_string(), Value::String(rfc3164_timestamp(&captures[2], Utc::now().date_naive())));
+		if let Some(hostname) = captures.get(3) {
+			log_entry.insert("hostname".to_string(), Value::String(hostname.as_str().to_string()));
+		}
+		log_entry.insert("appname".to_string(), Value::String(captures[4].to_string()));
+		if let Some(procid) = captures.get(5) {
+			log_entry.insert("procid".to_string(), Value::String(procid.as_str().to_string()));
+		}
+		insert_message(&mut log_entry, &captures[6]);
+		Some(log_entry)
+	} else {
+		let captures = RFC3164_WITHOUT_TAG.captures(line)?;
+		let mut log_entry = parse_priority(&captures[1])?;
+		log_entry.insert("timestamp".to_string(), Value::String(rfc3164_timestamp(&captures[2], Utc::now().date_naive())));
+		insert_message(&mut log_entry, &captures[3]);
+		Some(log_entry)
+	}
+}
+
+/// RFC 3164 timestamps like `Oct 11 22:14:15` have no year and no time zone. They are converted to `2025-10-11T22:14:15`
+/// with the year of today, or of the year before for dates after tomorrow or not in this year (February 29th), so they
+/// can be compared to other timestamps.
+/// Timestamps that are no valid date in that year are kept as they are.
+fn rfc3164_timestamp(timestamp: &str, today: NaiveDate) -> String {
+	let in_year = |year: i32| NaiveDateTime::parse_from_str(&format!("{year} {timestamp}"), "%Y %b %e %H:%M:%S").ok();
+	let datetime = match in_year(today.year()) {
+		Some(datetime) if datetime.date() <= today + Days::new(1) => Some(datetime),
+		_ => in_year(today.year() - 1),
+	};
+	match datetime {
+		Some(datetime) => datetime.format("%Y-%m-%dT%H:%M:%S").to_string(),
+		None => timestamp.to_string(),
+	}
+}
+
+fn parse_priority(priority: &str) -> Option<Map<String, Value>> {
+	let priority: usize = priority.parse().ok()?;
+	let facility = FACILITIES.get(priority / 8)?;
+	let (severity, level) = SEVERITIES[priority % 8];
+
+	let mut log_entry = Map::new();
+	log_entry.insert("facility".to_string(), Value::String(facility.to_string()));
+	log_entry.insert("severity".to_string(), Value::String(severity.to_string()));
+	log_entry.insert("level".to_string(), Value::String(level.to_string()));
+	Some(log_entry)
+}
+
+fn insert_unless_nil(log_entry: &mut Map<String, Value>, key: &str, value: &str) {
+	if value != "-" {
+		log_entry.insert(key.to_string(), Value::String(value.to_string()));
+	}
+}
+
+fn insert_message(log_entry: &mut Map<String, Value>, message: &str) {
+	match serde_json::from_str::<Value>(message) {
+		Ok(Value::Object(payload)) => log_entry.extend(payload),
+		_ => {
+			log_entry.insert("message".to_string(), Value::String(message.to_string()));
+		}
+	}
+}
+
+/// Parses `[id param="value" ...][id2 ...]` or the nil value `-` and returns the rest of the line as message.
+fn parse_structured_data(rest: &str) -> Option<(Map<String, Value>, &str)> {
+	let mut structured_data = Map::new();
+	if let Some(message) = rest.strip_prefix('-') {
+		return Some((structured_data, message.strip_prefix(' ').unwrap_or(message)));
+	}
+
+	let mut rest = rest;
+	while let Some(element) = rest.strip_prefix('[') {
+		let (id, mut remaining) = element.split_at(element.find([' ', ']'])?);
+		let mut params = Map::new();
+		loop {
+			remaining = remaining.trim_start_matches(' ');
+			if let Some(after_element) = remaining.strip_prefix(']') {
+				remaining = after_element;
+				break;
+			}
+			let (name, value_start) = remaining.split_once("=\"")?;
+			let (value, after_value) = parse_param_value(value_start)?;
+			params.insert(name.to_string(), Value::String(value));
+			remaining = after_value;
+		}
+		structured_data.insert(id.to_string(), Value::Object(params));
+		rest = remaining;
+	}
+
+	if structured_data.is_empty() {
+		return None;
+	}
+	Some((structured_data, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+/// Reads a param value after its opening quote and returns it unescaped together with the text after the closing quote.
+fn parse_param_value(value_start: &str) -> Option<(String, &str)> {
+	let mut value = String::new();
+	let mut chars = value_start.char_indices();
+	while let Some((index, c)) = chars.next() {
+		match c {
+			'"' => return Some((value, &value_start[index + 1..])),
+			'\\' => match chars.next()? {
+				(_, escaped @ ('"' | '\\' | ']')) => value.push(escaped),
+				(_, other) => {
+					value.push('\\');
+					value.push(other);
+				}
+			},
+			c => value.push(c),
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn parsed(line: &str) -> Value {
+		Value::Object(parse(line).unwrap())
+	}
+
+	#[test]
+	fn rfc5424() {
+		assert_eq!(
+			parsed("<34>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 - 'su root' failed for lonvick on /dev/pts/8"),
+			json!({
+				"facility": "auth",
+				"severity": "crit",
+				"level": "fatal",
+				"version": "1",
+				"timestamp": "2003-10-11T22:14:15.003Z",
+				"hostname": "mymachine.example.com",
+				"appname": "su",
+				"msgid": "ID47",
+				"message": "'su root' failed for lonvick on /dev/pts/8"
+			})
+		);
+	}
+
+	#[test]
+	fn rfc5424_structured_data() {
+		assert_eq!(
+			parsed(r#"<165>1 2003-10-11T22:14:15.003Z host app 1234 - [exampleSDID@32473 iut="3" eventID="10\"11\]"][origin ip="192.0.2.1"] An application event"#),
+			json!({
+				"facility": "local4",
+				"severity": "notice",
+				"level": "info",
+				"version": "1",
+				"timestamp": "2003-10-11T22:14:15.003Z",
+				"hostname": "host",
+				"appname": "app",
+				"procid": "1234",
+				"sd": {
+					"exampleSDID@32473": {"iut": "3", "eventID": "10\"11]"},
+					"origin": {"ip": "192.0.2.1"}
+				},
+				"message": "An application event"
+			})
+		);
+	}
+
+	#[test]
+	fn rfc5424_json_message() {
+		assert_eq!(
+			parsed("<14>1 2003-10-11T22:14:15.003Z host app - - - \u{feff}{\"msg\": \"started\", \"port\": 8080}"),
+			json!({
+				"facility": "user",
+				"severity": "info",
+				"level": "info",
+				"version": "1",
+				"timestamp": "2003-10-11T22:14:15.003Z",
+				"hostname": "host",
+				"appname": "app",
+				"msg": "started",
+				"port": 8080
+			})
+		);
+	}
+
+	#[test]
+	fn rfc3164() {
+		assert_eq!(
+			parsed("<13>Oct 11 22:14:15 mymachine su[123]: 'su root' failed"),
+			json!({
+				"facility": "user",
+				"severity": "notice",
+				"level": "info",
+				"timestamp": rfc3164_timestamp("Oct 11 22:14:15", Utc::now().date_naive()),
+				"hostname": "mymachine",
+				"appname": "su",
+				"procid": "123",
+				"message": "'su root' failed"
+			})
+		);
+		assert_eq!(
+			parsed("<11>Oct  1 22:14:15 kernel: oops"),
+			json!({
+				"facility": "user",
+				"severity": "err",
+				"level": "error",
+				"timestamp": rfc3164_timestamp("Oct  1 22:14:15", Utc::now().date_naive()),
+				"appname": "kernel",
+				"message": "oops"
+			})
+		);
+	}
+
+	#[test]
+	fn rfc3164_timestamps() {
+		let today = NaiveDate::from_ymd_opt(2025, 10, 12).unwrap();
+		assert_eq!(rfc3164_timestamp("Oct 11 22:14:15", today), "2025-10-11T22:14:15");
+		assert_eq!(rfc3164_timestamp("Oct  1 22:14:15", today), "2025-10-01T22:14:15");
+		assert_eq!(rfc3164_timestamp("Oct 13 00:00:01", today), "2025-10-13T00:00:01");
+		assert_eq!(rfc3164_timestamp("Dec 31 23:59:59", today), "2024-12-31T23:59:59");
+		assert_eq!(rfc3164_timestamp("Feb 29 10:00:00", today), "2024-02-29T10:00:00");
+		assert_eq!(rfc3164_timestamp("Feb 30 10:00:00", today), "Feb 30 10:00:00");
+		assert!(crate::time::parse_timestamp(&rfc3164_timestamp("Oct 11 22:14:15", today), crate::time::EpochUnit::Auto).is_some());
+	}
+
+	#[test]
+	fn no_syslog() {
+		assert!(parse("Oct 11 22:14:15 mymachine su: no priority").is_none());
+		assert!(parse("<999>1 2003-10-11T22:14:15.003Z host app - - - invalid priority").is_none());
+		assert!(parse("<html>").is_none());
+	}
+}
@@ -1,80 +1,153 @@
-use handlebars::{Handlebars, handlebars_helper, no_escape};
-use std::convert::TryInto;
-use yansi::{Color, Paint};
+use crate::compiled_template::{CompiledTemplate, Fields};
+use crate::substitution::Substitution;
+use handlebars::{Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason, ScopedJson, handlebars_helper, no_escape};
+use serde::Serialize;
+use serde_json::{Map, Value};
 
-pub static DEFAULT_MAIN_LINE_FORMAT: &str = "{{bold(fixed_size 19 fblog_timestamp)}} {{level_style (uppercase (fixed_size 5 fblog_level))}}:{{#if fblog_prefix}} {{bold(cyan fblog_prefix)}}{{/if}} {{fblog_message}}";
+pub static DEFAULT_MAIN_LINE_FORMAT: &str = "{{#if fblog_source_tag}}{{fblog_source_tag}} {{/if}}{{bold(fixed_size 19 fblog_timestamp)}} {{level_style (uppercase (fixed_size 5 fblog_level))}}:{{#if fblog_prefix}} {{bold(cyan fblog_prefix)}}{{/if}} {{fblog_message}}";
 pub static DEFAULT_ADDITIONAL_VALUE_FORMAT: &str = "{{bold (color_rgb 150 150 150 (min_size 25 key))}}: {{value}}";
 
-pub fn fblog_handlebar_registry(main_line_format: String, additional_value_format: String) -> Handlebars<'static> {
-	handlebars_helper!(bold: |t: str| {
-			format!("{}", t.bold())
-	});
-
-	handlebars_helper!(cyan: |t: str| {
-			format!("{}", t.cyan())
-	});
-
-	handlebars_helper!(yellow: |t: str| {
-			format!("{}", t.yellow())
-	});
-
-	handlebars_helper!(red: |t: str| {
-			format!("{}", t.red())
-	});
-
-	handlebars_helper!(blue: |t: str| {
-			format!("{}", t.blue())
-	});
-
-	handlebars_helper!(purple: |t: str| {
-			format!("{}", t.magenta())
-	});
-
-	handlebars_helper!(green: |t: str| {
-			format!("{}", t.green())
-	});
-
-	handlebars_helper!(color_rgb: |r: u64, g: u64, b: u64, t: str| {
-			format!("{}", t.rgb(r.try_into().unwrap(), g.try_into().unwrap(), b.try_into().unwrap()))
-	});
-
-	handlebars_helper!(uppercase: |t: str| {
-			t.to_uppercase()
-	});
-
-	handlebars_helper!(level_style: |level: str| {
-			let color = match level.trim().to_lowercase().as_ref() {
-					"trace" => Color::Cyan,
-					"debug" => Color::Blue,
-					"info" => Color::Green,
-					"warn" | "warning" => Color::Yellow,
-					"error" | "err" => Color::Red,
-					"fatal" => Color::Magenta,
-					_ => Color::Magenta,
-			};
-			format!("{}", level.fg(color).bold())
-	});
-
-	handlebars_helper!(fixed_size: |isize: u64, t: str| {
-			let mut x = t.to_string();
-			let size = isize.try_into().expect("should fit");
-			x.truncate(size);
-			if x.len() < size {
-				 format!("{}{}", " ".repeat(size - x.len()), x)
-			} else {
-				x
-			}
-	});
-
-	handlebars_helper!(min_size: |isize: u64, t: str| {
-			let x = t.to_string();
-			let size = isize.try_into().expect("should fit");
-			if x.len() < size {
-				 format!("{}{}", " ".repeat(size - x.len()), x)
-			} else {
-				x
-			}
-	});
+/// The helpers available in templates, shared by handlebars and the compiled templates.
+pub mod helpers {
+	use std::convert::TryInto;
+	use yansi::{Color, Paint};
+
+	pub fn bold(t: &str) -> String {
+		format!("{}", t.bold())
+	}
+
+	pub fn cyan(t: &str) -> String {
+		format!("{}", t.cyan())
+	}
+
+	pub fn yellow(t: &str) -> String {
+		format!("{}", t.yellow())
+	}
+
+	pub fn red(t: &str) -> String {
+		format!("{}", t.red())
+	}
+
+	pub fn blue(t: &str) -> String {
+		format!("{}", t.blue())
+	}
+
+	pub fn purple(t: &str) -> String {
+		format!("{}", t.magenta())
+	}
+
+	pub fn green(t: &str) -> String {
+		format!("{}", t.green())
+	}
+
+	pub fn color_rgb(r: u64, g: u64, b: u64, t: &str) -> String {
+		format!("{}", t.rgb(r.try_into().unwrap(), g.try_into().unwrap(), b.try_into().unwrap()))
+	}
+
+	pub fn uppercase(t: &str) -> String {
+		t.to_uppercase()
+	}
+
+	pub fn level_style(level: &str) -> String {
+		let color = match level.trim().to_lowercase().as_ref() {
+			"trace" => Color::Cyan,
+			"debug" => Color::Blue,
+			"info" => Color::Green,
+			"warn" | "warning" => Color::Yellow,
+			"error" | "err" => Color::Red,
+			"fatal" => Color::Magenta,
+			_ => Color::Magenta,
+		};
+		format!("{}", level.fg(color).bold())
+	}
+
+	pub fn fixed_size(isize: u64, t: &str) -> String {
+		let mut x = t.to_string();
+		let size = isize.try_into().expect("should fit");
+		x.truncate(size);
+		if x.len() < size { format!("{}{}", " ".repeat(size - x.len()), x) } else { x }
+	}
+
+	pub fn min_size(isize: u64, t: &str) -> String {
+		let x = t.to_string();
+		let size = isize.try_into().expect("should fit");
+		if x.len() < size { format!("{}{}", " ".repeat(size - x.len()), x) } else { x }
+	}
+}
+
+/// The templates for the main line and additional values. Templates that only use the fblog helpers are rendered by a
+/// compiled template, all others by handlebars.
+pub struct Templates {
+	handlebars: Handlebars<'static>,
+	main_line: Option<CompiledTemplate>,
+	additional_value: Option<CompiledTemplate>,
+}
+
+impl Templates {
+	pub fn new(main_line_format: String, additional_value_format: String, substitution: Substitution) -> Templates {
+		let handlebars = fblog_handlebar_registry(main_line_format, additional_value_format, substitution);
+		let compile = |name| handlebars.get_template(name).and_then(CompiledTemplate::compile);
+		let main_line = compile("main_line");
+		let additional_value = compile("additional_value");
+		Templates {
+			handlebars,
+			main_line,
+			additional_value,
+		}
+	}
+
+	pub fn render_main_line<T: Serialize + Fields>(&self, data: &T) -> Result<String, RenderError> {
+		render(&self.handlebars, "main_line", self.main_line.as_ref(), data)
+	}
+
+	pub fn render_additional_value<T: Serialize + Fields>(&self, data: &T) -> Result<String, RenderError> {
+		render(&self.handlebars, "additional_value", self.additional_value.as_ref(), data)
+	}
+}
+
+fn render<T: Serialize + Fields>(handlebars: &Handlebars, name: &str, compiled: Option<&CompiledTemplate>, data: &T) -> Result<String, RenderError> {
+	match compiled.and_then(|compiled| compiled.render(data)) {
+		Some(rendered) => Ok(rendered),
+		None => handlebars.render(name, data),
+	}
+}
+
+/// `{{substitute field}}` substitutes the placeholders of a field like `--substitute` does for the message.
+struct SubstituteHelper(Substitution);
+
+impl HelperDef for SubstituteHelper {
+	fn call_inner<'reg: 'rc, 'rc>(
+		&self,
+		h: &Helper<'rc>,
+		_: &'reg Handlebars<'reg>,
+		ctx: &'rc Context,
+		_: &mut RenderContext<'reg, 'rc>,
+	) -> Result<ScopedJson<'rc>, RenderError> {
+		let param = h.param(0).ok_or(RenderErrorReason::ParamNotFoundForIndex("substitute", 0))?;
+		let text = param
+			.value()
+			.as_str()
+			.ok_or_else(|| RenderErrorReason::ParamTypeMismatchForName("substitute", "0".to_string(), "string".to_string()))?;
+		let empty = Map::new();
+		let log_entry = ctx.data().as_object().unwrap_or(&empty);
+		let substituted = self.0.apply(text, log_entry).unwrap_or_else(|| text.to_string());
+		Ok(ScopedJson::Derived(Value::String(substituted)))
+	}
+}
+
+pub fn fblog_handlebar_registry(main_line_format: String, additional_value_format: String, substitution: Substitution) -> Handlebars<'static> {
+	handlebars_helper!(bold: |t: str| helpers::bold(t));
+	handlebars_helper!(cyan: |t: str| helpers::cyan(t));
+	handlebars_helper!(yellow: |t: str| helpers::yellow(t));
+	handlebars_helper!(red: |t: str| helpers::red(t));
+	handlebars_helper!(blue: |t: str| helpers::blue(t));
+	handlebars_helper!(purple: |t: str| helpers::purple(t));
+	handlebars_helper!(green: |t: str| helpers::green(t));
+	handlebars_helper!(color_rgb: |r: u64, g: u64, b: u64, t: str| helpers::color_rgb(r, g, b, t));
+	handlebars_helper!(uppercase: |t: str| helpers::uppercase(t));
+	handlebars_helper!(level_style: |level: str| helpers::level_style(level));
+	handlebars_helper!(fixed_size: |isize: u64, t: str| helpers::fixed_size(isize, t));
+	handlebars_helper!(min_size: |isize: u64, t: str| helpers::min_size(isize, t));
 
 	let mut reg = Handlebars::new();
 	reg.register_escape_fn(Box::new(no_escape));
@@ -92,6 +165,7 @@ pub fn fblog_handlebar_registry(main_line_format: String, additional_value_forma
 	reg.register_helper("purple", Box::new(purple));
 	reg.register_helper("green", Box::new(green));
 	reg.register_helper("color_rgb", Box::new(color_rgb));
+	reg.register_helper("substitute", Box::new(SubstituteHelper(substitution)));
 
 	reg.register_template_string("main_line", main_line_format).expect("Template invalid");
 	reg
@@ -1,6 +1,28 @@
-use chrono::{LocalResult, TimeZone, Utc};
+use chrono::{DateTime, LocalResult, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 
-fn timestamp_to_iso8601(timestamp: i64) -> Option<String> {
+/// The unit of numeric timestamps. `Auto` reads integers as seconds or milliseconds, whichever is closer to now,
+/// and decimals as seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EpochUnit {
+	#[default]
+	Auto,
+	Seconds,
+	Milliseconds,
+	Microseconds,
+	Nanoseconds,
+}
+
+static ZONED_FORMATS: &[&str] = &[
+	"%Y-%m-%dT%H:%M:%S%.f%z",
+	"%Y-%m-%d %H:%M:%S%.f%z",
+	"%Y-%m-%d %H:%M:%S%.f %z",
+	"%d/%b/%Y:%H:%M:%S %z",
+];
+static NAIVE_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M:%S,%3f", "%Y-%m-%dT%H:%M"];
+
+fn epoch_to_datetime(timestamp: i64) -> Option<DateTime<Utc>> {
 	let now = Utc::now();
 
 	let dt_secs = Utc.timestamp_opt(timestamp, 0);
@@ -11,25 +33,92 @@ fn timestamp_to_iso8601(timestamp: i64) -> Option<String> {
 			let diff_secs = (now - dt_secs).num_milliseconds().abs();
 			let diff_millis = (now - dt_millis).num_milliseconds().abs();
 
-			if diff_secs < diff_millis {
-				Some(dt_secs.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
-			} else {
-				Some(dt_millis.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
-			}
+			if diff_secs < diff_millis { Some(dt_secs) } else { Some(dt_millis) }
 		}
-		(LocalResult::Single(dt_secs), _) => Some(dt_secs.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
-		(_, LocalResult::Single(dt_millis)) => Some(dt_millis.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+		(LocalResult::Single(dt_secs), _) => Some(dt_secs),
+		(_, LocalResult::Single(dt_millis)) => Some(dt_millis),
 		_ => None, // Neither is valid (or ambiguous due to time zone transitions)
 	}
 }
 
-pub fn try_convert_timestamp_to_readable(input: String) -> String {
+fn timestamp_to_iso8601(timestamp: i64) -> Option<String> {
+	epoch_to_datetime(timestamp).map(datetime_to_iso8601)
+}
+
+fn datetime_to_iso8601(dt: DateTime<Utc>) -> String {
+	dt.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn parse_epoch(input: &str, unit: EpochUnit) -> Option<DateTime<Utc>> {
+	let nanos_per_unit: i64 = match unit {
+		EpochUnit::Auto => {
+			if let Ok(epoch) = input.parse::<i64>() {
+				return epoch_to_datetime(epoch);
+			}
+			let epoch = input.parse::<f64>().ok()?;
+			return Utc.timestamp_millis_opt((epoch * 1000.0) as i64).single();
+		}
+		EpochUnit::Seconds => 1_000_000_000,
+		EpochUnit::Milliseconds => 1_000_000,
+		EpochUnit::Microseconds => 1_000,
+		EpochUnit::Nanoseconds => 1,
+	};
+	let nanos = match input.parse::<i64>() {
+		Ok(epoch) => epoch.checked_mul(nanos_per_unit)?,
+		Err(_) => {
+			let nanos = input.parse::<f64>().ok()? * nanos_per_unit as f64;
+			if !nanos.is_finite() || nanos.abs() >= i64::MAX as f64 {
+				return None;
+			}
+			nanos as i64
+		}
+	};
+	Some(Utc.timestamp_nanos(nanos))
+}
+
+/// Parses the timestamp of a log entry so entries can be compared in time.
+/// Timestamps without a time zone are treated as UTC.
+pub fn parse_timestamp(input: &str, epoch_unit: EpochUnit) -> Option<DateTime<Utc>> {
+	let input = input.trim();
+	if input.is_empty() {
+		return None;
+	}
+
+	if input.parse::<f64>().is_ok() {
+		return parse_epoch(input, epoch_unit);
+	}
+
+	if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+		return Some(dt.with_timezone(&Utc));
+	}
+
+	for format in ZONED_FORMATS {
+		if let Ok(dt) = DateTime::parse_from_str(input, format) {
+			return Some(dt.with_timezone(&Utc));
+		}
+	}
+
+	for format in NAIVE_FORMATS {
+		if let Ok(dt) = NaiveDateTime::parse_from_str(input, format) {
+			return Some(dt.and_utc());
+		}
+	}
+
+	DateTime::parse_from_rfc2822(input).ok().map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Converts numeric timestamps to ISO 8601. With `EpochUnit::Auto` only integers are converted.
+pub fn try_convert_timestamp_to_readable(input: String, epoch_unit: EpochUnit) -> String {
 	if input.is_empty() {
 		return input;
 	}
 
-	if let Some(parsed) = input.parse::<i64>().ok().and_then(timestamp_to_iso8601) {
-		return parsed;
+	if epoch_unit == EpochUnit::Auto {
+		if let Some(parsed) = input.parse::<i64>().ok().and_then(timestamp_to_iso8601) {
+			return parsed;
+		}
+	} else if let Some(parsed) = parse_epoch(&input, epoch_unit) {
+		return datetime_to_iso8601(parsed);
 	}
 
 	input
@@ -37,7 +126,7 @@ pub fn try_convert_timestamp_to_readable(input: String) -> String {
 
 #[cfg(test)]
 mod tests {
-	use chrono::DateTime;
+	use chrono::{DateTime, Timelike};
 
 	use super::*;
 
@@ -59,10 +148,59 @@ mod tests {
 
 	#[test]
 	fn test_try_convert() {
-		assert_eq!(try_convert_timestamp_to_readable("1716292213381".to_string()), "2024-05-21T11:50:13.381Z");
-		assert_eq!(try_convert_timestamp_to_readable("1716292213".to_string()), "2024-05-21T11:50:13.000Z");
-		assert_eq!(try_convert_timestamp_to_readable("bla".to_string()), "bla");
-		assert_eq!(try_convert_timestamp_to_readable("1234bla".to_string()), "1234bla");
-		assert_eq!(try_convert_timestamp_to_readable("".to_string()), "");
+		assert_eq!(
+			try_convert_timestamp_to_readable("1716292213381".to_string(), EpochUnit::Auto),
+			"2024-05-21T11:50:13.381Z"
+		);
+		assert_eq!(
+			try_convert_timestamp_to_readable("1716292213".to_string(), EpochUnit::Auto),
+			"2024-05-21T11:50:13.000Z"
+		);
+		assert_eq!(try_convert_timestamp_to_readable("bla".to_string(), EpochUnit::Auto), "bla");
+		assert_eq!(try_convert_timestamp_to_readable("1234bla".to_string(), EpochUnit::Auto), "1234bla");
+		assert_eq!(try_convert_timestamp_to_readable("".to_string(), EpochUnit::Auto), "");
+	}
+
+	#[test]
+	fn test_convert_epoch_units() {
+		assert_eq!(
+			try_convert_timestamp_to_readable("1716290489.536".to_string(), EpochUnit::Auto),
+			"1716290489.536"
+		);
+		assert_eq!(
+			try_convert_timestamp_to_readable("1716290489.536".to_string(), EpochUnit::Seconds),
+			"2024-05-21T11:21:29.536Z"
+		);
+		assert_eq!(
+			try_convert_timestamp_to_readable("1716290489536".to_string(), EpochUnit::Milliseconds),
+			"2024-05-21T11:21:29.536Z"
+		);
+		assert_eq!(
+			try_convert_timestamp_to_readable("1716290489536000".to_string(), EpochUnit::Microseconds),
+			"2024-05-21T11:21:29.536Z"
+		);
+		assert_eq!(
+			try_convert_timestamp_to_readable("1716290489536000000".to_string(), EpochUnit::Nanoseconds),
+			"2024-05-21T11:21:29.536Z"
+		);
+		assert_eq!(try_convert_timestamp_to_readable("1e300".to_string(), EpochUnit::Seconds), "1e300");
+		assert_eq!(try_convert_timestamp_to_readable("2024-05-21".to_string(), EpochUnit::Seconds), "2024-05-21");
+	}
+
+	#[test]
+	fn test_parse_timestamp() {
+		let expected = DateTime::parse_from_rfc3339("2024-05-21T11:21:29.536Z").unwrap().with_timezone(&Utc);
+		assert_eq!(parse_timestamp("2024-05-21T11:21:29.536Z", EpochUnit::Auto), Some(expected));
+		assert_eq!(parse_timestamp("2024-05-21T13:21:29.536+02:00", EpochUnit::Auto), Some(expected));
+		assert_eq!(parse_timestamp("2024-05-21 11:21:29.536", EpochUnit::Auto), Some(expected));
+		assert_eq!(parse_timestamp("2024-05-21 11:21:29,536", EpochUnit::Auto), Some(expected));
+		assert_eq!(parse_timestamp("1716290489536", EpochUnit::Auto), Some(expected));
+		assert_eq!(parse_timestamp("1716290489.536", EpochUnit::Auto), Some(expected));
+		assert_eq!(
+			parse_timestamp("21/May/2024:13:21:29 +0200", EpochUnit::Auto),
+			Some(expected.with_nanosecond(0).unwrap())
+		);
+		assert_eq!(parse_timestamp("bla", EpochUnit::Auto), None);
+		assert_eq!(parse_timestamp("", EpochUnit::Auto), None);
 	}
 }
@@ -0,0 +1,165 @@
+use crate::time::{EpochUnit, parse_timestamp};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+	Invalid(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Invalid(input) => write!(f, "`{input}` is neither a timestamp nor a duration like `15m` or `1h30m`"),
+		}
+	}
+}
+
+/// What happens to log entries whose timestamp is missing or cannot be parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unparsed {
+	Keep,
+	Drop,
+	/// Shown when the previous log entry is shown, e.g. for continuation entries of a stack trace.
+	Attach,
+}
+
+impl Unparsed {
+	pub fn from_name(name: &str) -> Option<Unparsed> {
+		match name {
+			"keep" => Some(Unparsed::Keep),
+			"drop" => Some(Unparsed::Drop),
+			"attach" => Some(Unparsed::Attach),
+			_ => None,
+		}
+	}
+}
+
+/// Shows log entries with a timestamp at or after `since` and before `until`.
+pub struct TimeRange {
+	since: Option<DateTime<Utc>>,
+	until: Option<DateTime<Utc>>,
+	unparsed: Unparsed,
+	/// Whether the last log entry was shown, entries are only attached with a single thread.
+	previous_shown: AtomicBool,
+}
+
+impl TimeRange {
+	pub fn new(since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>, unparsed: Unparsed) -> TimeRange {
+		TimeRange {
+			since,
+			until,
+			unparsed,
+			previous_shown: AtomicBool::new(false),
+		}
+	}
+
+	/// Log entries are attached to the previous entry, so they must be processed in order.
+	pub fn attaches_unparsed(&self) -> bool {
+		self.unparsed == Unparsed::Attach
+	}
+
+	pub fn show(&self, timestamp: Option<DateTime<Utc>>) -> bool {
+		let show = match timestamp {
+			Some(timestamp) => self.since.is_none_or(|since| since <= timestamp) && self.until.is_none_or(|until| timestamp < until),
+			None => match self.unparsed {
+				Unparsed::Keep => true,
+				Unparsed::Drop => false,
+				Unparsed::Attach => return self.previous_shown.load(Ordering::Relaxed),
+			},
+		};
+		self.previous_shown.store(show, Ordering::Relaxed);
+		show
+	}
+}
+
+/// Parses a bound of the time range, either a timestamp or a duration before `now`.
+/// Timestamps without a time zone are treated as UTC like the timestamps of log entries.
+pub fn parse_bound(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, Error> {
+	let input = input.trim();
+	if let Some(duration) = parse_duration(input) {
+		return Ok(now - duration);
+	}
+	if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+		return Ok(date.and_time(Default::default()).and_utc());
+	}
+	parse_timestamp(input, EpochUnit::Auto).ok_or_else(|| Error::Invalid(input.to_string()))
+}
+
+/// Parses durations like `90s`, `15m` or `1h30m`. Units are `ms`, `s`, `m`, `h`, `d` and `w`.
+fn parse_duration(input: &str) -> Option<TimeDelta> {
+	let mut duration = TimeDelta::zero();
+	let mut rest = input;
+	while !rest.is_empty() {
+		let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+		let amount: i64 = rest[..digits].parse().ok()?;
+		rest = &rest[digits..];
+		let unit_length = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+		let part = match &rest[..unit_length] {
+			"ms" => TimeDelta::try_milliseconds(amount),
+			"s" => TimeDelta::try_seconds(amount),
+			"m" => TimeDelta::try_minutes(amount),
+			"h" => TimeDelta::try_hours(amount),
+			"d" => TimeDelta::try_days(amount),
+			"w" => TimeDelta::try_weeks(amount),
+			_ => None,
+		}?;
+		duration = duration.checked_add(&part)?;
+		rest = &rest[unit_length..];
+	}
+	(!input.is_empty()).then_some(duration)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn utc(input: &str) -> DateTime<Utc> {
+		DateTime::parse_from_rfc3339(input).unwrap().with_timezone(&Utc)
+	}
+
+	fn timestamp(input: &str) -> Option<DateTime<Utc>> {
+		parse_timestamp(input, EpochUnit::Auto)
+	}
+
+	#[test]
+	fn parse_bounds() {
+		let now = utc("2024-05-21T12:00:00Z");
+		assert_eq!(parse_bound("15m", now), Ok(utc("2024-05-21T11:45:00Z")));
+		assert_eq!(parse_bound("1h30m", now), Ok(utc("2024-05-21T10:30:00Z")));
+		assert_eq!(parse_bound("2d", now), Ok(utc("2024-05-19T12:00:00Z")));
+		assert_eq!(parse_bound("2024-05-21T10:00", now), Ok(utc("2024-05-21T10:00:00Z")));
+		assert_eq!(parse_bound("2024-05-21T10:00:00+02:00", now), Ok(utc("2024-05-21T08:00:00Z")));
+		assert_eq!(parse_bound("2024-05-20", now), Ok(utc("2024-05-20T00:00:00Z")));
+		assert_eq!(parse_bound("15x", now), Err(Error::Invalid("15x".to_string())));
+		assert_eq!(parse_bound("m", now), Err(Error::Invalid("m".to_string())));
+		assert_eq!(parse_bound("", now), Err(Error::Invalid("".to_string())));
+	}
+
+	#[test]
+	fn show_entries_in_range() {
+		let time_range = TimeRange::new(Some(utc("2024-05-21T10:00:00Z")), Some(utc("2024-05-21T11:00:00Z")), Unparsed::Keep);
+		assert!(!time_range.show(timestamp("2024-05-21T09:59:59Z")));
+		assert!(time_range.show(timestamp("2024-05-21T10:00:00Z")));
+		assert!(time_range.show(timestamp("2024-05-21 12:30:00+02:00")));
+		assert!(!time_range.show(timestamp("2024-05-21T11:00:00Z")));
+		assert!(time_range.show(timestamp("1716286500")));
+		assert!(time_range.show(timestamp("no timestamp")));
+		assert!(time_range.show(None));
+	}
+
+	#[test]
+	fn unparsed_entries() {
+		let since = Some(utc("2024-05-21T10:00:00Z"));
+		assert!(!TimeRange::new(since, None, Unparsed::Drop).show(None));
+
+		let time_range = TimeRange::new(since, None, Unparsed::Attach);
+		assert!(!time_range.show(None));
+		assert!(time_range.show(timestamp("2024-05-21T10:00:00Z")));
+		assert!(time_range.show(None));
+		assert!(time_range.show(timestamp("invalid")));
+		assert!(!time_range.show(timestamp("2024-05-21T09:00:00Z")));
+		assert!(!time_range.show(None));
+	}
+}